@@ -0,0 +1,292 @@
+//! Moving vehicle markers smoothly between the updates of the feeds.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use walkers::Position;
+
+use crate::{
+    geo,
+    gtfs::Schedule,
+    vehicle::{Vehicle, VehicleKey},
+};
+
+/// How the markers move between the updates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Animation {
+    /// Jump to the new position as soon as it is known.
+    Off,
+    /// Glide from the previous position to the new one, taking as long as the updates take
+    /// to come. Markers lag behind by one update, but never go where the vehicle was not.
+    #[default]
+    Interpolate,
+    /// Estimate where the vehicle is now, from its last known position, speed and heading.
+    /// Vehicles snapped onto their routes keep following them.
+    DeadReckoning,
+}
+
+/// Shortest and longest time, in seconds, a marker takes to reach the new position.
+const MIN_DURATION: f64 = 0.5;
+const MAX_DURATION: f64 = 10.0;
+
+/// Time, in seconds, a dead reckoned marker takes to catch up with the new position.
+const CATCH_UP: f64 = 1.0;
+
+/// Where a marker is heading to and since when. Times come from the egui frame clock.
+struct Leg {
+    from: Position,
+    to: Position,
+    started: f64,
+    duration: f64,
+}
+
+/// Keeps the state of the animation between frames.
+#[derive(Default)]
+pub struct Animator {
+    pub mode: Animation,
+    legs: HashMap<VehicleKey, Leg>,
+}
+
+impl Animator {
+    /// Where to draw the vehicles at the time `now` of the egui frame clock, and whether any
+    /// marker is still moving, so that another frame is needed.
+    pub fn positions(
+        &mut self,
+        vehicles: &HashMap<VehicleKey, Vehicle>,
+        schedule: &Schedule,
+        now: f64,
+    ) -> (HashMap<VehicleKey, Position>, bool) {
+        self.legs.retain(|id, _| vehicles.contains_key(id));
+
+        let mut moving = false;
+        let positions = vehicles
+            .iter()
+            .map(|(id, vehicle)| {
+                let target = vehicle.position();
+                let (position, still_moving) = match self.legs.get_mut(id) {
+                    Some(leg) => {
+                        if leg.to != target {
+                            // The update interval is learned from how often positions change.
+                            let duration = (now - leg.started).clamp(MIN_DURATION, MAX_DURATION);
+                            *leg = Leg {
+                                from: self.mode.position(leg, vehicle, schedule, now),
+                                to: target,
+                                started: now,
+                                duration,
+                            };
+                        }
+                        (
+                            self.mode.position(leg, vehicle, schedule, now),
+                            self.mode.moving(leg, vehicle, now),
+                        )
+                    }
+                    None => {
+                        self.legs.insert(
+                            id.clone(),
+                            Leg {
+                                from: target,
+                                to: target,
+                                started: now,
+                                duration: MAX_DURATION,
+                            },
+                        );
+                        (target, false)
+                    }
+                };
+                moving |= still_moving;
+                (id.clone(), position)
+            })
+            .collect();
+
+        (positions, moving)
+    }
+}
+
+impl Animation {
+    fn position(self, leg: &Leg, vehicle: &Vehicle, schedule: &Schedule, now: f64) -> Position {
+        let elapsed = now - leg.started;
+        match self {
+            Animation::Off => leg.to,
+            Animation::Interpolate => {
+                geo::lerp(leg.from, leg.to, (elapsed / leg.duration).clamp(0.0, 1.0))
+            }
+            Animation::DeadReckoning => {
+                let estimated = match (vehicle.speed(), vehicle.bearing()) {
+                    (Some(speed), Some(bearing)) => {
+                        // Going on for longer than one update would likely overshoot.
+                        let meters = speed / 3.6 * elapsed.clamp(0.0, leg.duration);
+                        let snapped = vehicle
+                            .fixes()
+                            .last()
+                            .is_some_and(|fix| fix.snapped.is_some());
+                        snapped
+                            .then(|| schedule.follow(&vehicle.line, leg.to, bearing, meters))
+                            .flatten()
+                            .unwrap_or_else(|| geo::advance(leg.to, bearing, meters))
+                    }
+                    _ => leg.to,
+                };
+                geo::lerp(leg.from, estimated, (elapsed / CATCH_UP).clamp(0.0, 1.0))
+            }
+        }
+    }
+
+    fn moving(self, leg: &Leg, vehicle: &Vehicle, now: f64) -> bool {
+        let elapsed = now - leg.started;
+        match self {
+            Animation::Off => false,
+            Animation::Interpolate => leg.from != leg.to && elapsed < leg.duration,
+            Animation::DeadReckoning => {
+                elapsed < CATCH_UP
+                    || (elapsed < leg.duration && vehicle.speed().is_some_and(|s| s > 0.0))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeDelta};
+
+    use super::*;
+    use crate::{testing, vehicle::Fix};
+
+    fn a() -> Position {
+        walkers::lat_lon(51.1000, 17.0300)
+    }
+
+    fn b() -> Position {
+        walkers::lat_lon(51.1010, 17.0300)
+    }
+
+    fn key() -> VehicleKey {
+        VehicleKey {
+            feed: 0,
+            id: "2401".to_string(),
+        }
+    }
+
+    /// Vehicle of line 1 which reported given fixes, ten seconds apart.
+    fn vehicles(fixes: &[(Position, Option<Position>)]) -> HashMap<VehicleKey, Vehicle> {
+        let time = DateTime::from_timestamp(1_760_000_000, 0).unwrap();
+        let mut vehicle = Vehicle::new("1".to_string());
+        for (n, (position, snapped)) in fixes.iter().enumerate() {
+            vehicle.update(
+                "1",
+                Fix {
+                    time: time + TimeDelta::seconds(10 * n as i64),
+                    position: *position,
+                    snapped: *snapped,
+                },
+            );
+        }
+        HashMap::from([(key(), vehicle)])
+    }
+
+    /// Positions and whether the marker moves, at the given times, for a vehicle which was
+    /// seen at the first fix at time 0.0, and at the second one at time 5.0.
+    fn animate(
+        mode: Animation,
+        schedule: &Schedule,
+        fixes: [(Position, Option<Position>); 2],
+        times: &[f64],
+    ) -> Vec<(Position, bool)> {
+        let mut animator = Animator {
+            mode,
+            ..Default::default()
+        };
+        let first = fixes[0].1.unwrap_or(fixes[0].0);
+        assert_eq!(
+            animator.positions(&vehicles(&fixes[..1]), schedule, 0.0),
+            (HashMap::from([(key(), first)]), false)
+        );
+        let moved = vehicles(&fixes);
+        times
+            .iter()
+            .map(|now| {
+                let (positions, moving) = animator.positions(&moved, schedule, *now);
+                (positions[&key()], moving)
+            })
+            .collect()
+    }
+
+    /// Moves from `a` to `b`, not snapped onto any route.
+    fn animate_off_route(mode: Animation, times: &[f64]) -> Vec<(Position, bool)> {
+        animate(
+            mode,
+            &Schedule::default(),
+            [(a(), None), (b(), None)],
+            times,
+        )
+    }
+
+    #[test]
+    fn interpolation_never_overshoots_and_stops() {
+        let times: Vec<f64> = (0..=40).map(|n| 5.0 + n as f64 * 0.25).collect();
+        let frames = animate_off_route(Animation::Interpolate, &times);
+
+        let leg = geo::distance(a(), b());
+        for (position, _) in &frames {
+            assert!(geo::distance(a(), *position) <= leg + 1e-6);
+            assert!(geo::distance(*position, b()) <= leg + 1e-6);
+        }
+
+        // Takes as long as the updates took to come, 5 seconds.
+        assert_eq!(frames[0], (a(), true));
+        assert!(frames[10].1);
+        assert_eq!(frames[20], (b(), false));
+        assert_eq!(frames[40], (b(), false));
+    }
+
+    #[test]
+    fn markers_jump_when_animation_is_off() {
+        assert_eq!(
+            animate_off_route(Animation::Off, &[5.0, 6.0]),
+            [(b(), false), (b(), false)]
+        );
+    }
+
+    #[test]
+    fn dead_reckoning_stops_after_one_update() {
+        let frames = animate_off_route(Animation::DeadReckoning, &[5.0, 7.0, 10.0, 20.0]);
+
+        assert!(frames[0].1);
+        assert!(frames[1].1);
+        assert!(!frames[2].1);
+        assert!(!frames[3].1);
+        // Does not go on past one update.
+        assert_eq!(frames[2].0, frames[3].0);
+    }
+
+    #[test]
+    fn dead_reckoning_follows_the_route_and_stops_at_its_end() {
+        let schedule = testing::schedule();
+        // Last leg of line 1, heading east to its terminus at Plac Grunwaldzki.
+        let (bend, terminus) = (
+            walkers::lat_lon(51.1080, 17.0400),
+            walkers::lat_lon(51.1120, 17.0600),
+        );
+        let on_route = |t| {
+            let position = geo::lerp(bend, terminus, t);
+            (position, schedule.snap("1", position, 30.0))
+        };
+        // About 100 km/h, so going straight on for 5 seconds would leave the route.
+        let fixes = [on_route(0.75), on_route(0.95)];
+        assert!(fixes.iter().all(|(_, snapped)| snapped.is_some()));
+
+        let times: Vec<f64> = (0..=60).map(|n| 5.0 + n as f64 * 0.25).collect();
+        let frames = animate(Animation::DeadReckoning, &schedule, fixes, &times);
+
+        for (position, _) in &frames {
+            let (_, off_route) = geo::closest_on_polyline(&[bend, terminus], *position).unwrap();
+            assert!(off_route < 1.0, "{off_route} m off the route");
+            assert!(geo::distance(bend, *position) <= geo::distance(bend, terminus) + 1e-6);
+        }
+
+        // Reaches the terminus before the next update is due, and stays there.
+        assert!(frames[0].1);
+        assert!(geo::distance(frames[20].0, terminus) < 1e-6);
+        assert!(!frames[20].1);
+        assert_eq!(frames[20].0, frames[60].0);
+    }
+}
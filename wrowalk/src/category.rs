@@ -0,0 +1,118 @@
+//! Kinds of vehicles, drawn differently on the map.
+
+use egui::Color32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Category {
+    Tram,
+    Bus,
+    NightBus,
+    /// Special and replacement lines, e.g. buses replacing trams during works.
+    Special,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] = [
+        Category::Tram,
+        Category::Bus,
+        Category::NightBus,
+        Category::Special,
+    ];
+
+    /// From the `route_type` of a GTFS route, including the extended route types.
+    pub fn from_route_type(route_type: u32) -> Option<Self> {
+        match route_type {
+            0 | 900..=999 => Some(Category::Tram),
+            705 => Some(Category::NightBus),
+            714 => Some(Category::Special),
+            3 | 700..=799 | 200..=299 => Some(Category::Bus),
+            _ => None,
+        }
+    }
+
+    /// Of a line in the timetable. The `route_type` tells trams from buses, but Wrocław uses
+    /// only the basic types, so buses are told apart by the name of the line.
+    pub fn of(line: &str, route_type: u32) -> Self {
+        match Self::from_route_type(route_type) {
+            Some(Category::Bus) => match Self::guess(line) {
+                Category::Tram => Category::Bus,
+                category => category,
+            },
+            Some(category) => category,
+            None => Self::guess(line),
+        }
+    }
+
+    /// Guess from the name of the line, following the numbering of MPK Wrocław, where trams
+    /// are numbered below 100, night buses are 2xx and replacement lines start with "Z".
+    pub fn guess(line: &str) -> Self {
+        if line.starts_with(['Z', 'z']) {
+            return Category::Special;
+        }
+
+        // Circular tram lines are called "0L" and "0P".
+        if line.starts_with('0') {
+            return Category::Tram;
+        }
+
+        match line.parse::<u32>() {
+            Ok(..100) => Category::Tram,
+            Ok(200..300) => Category::NightBus,
+            _ => Category::Bus,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Tram => "Tram",
+            Category::Bus => "Bus",
+            Category::NightBus => "Night bus",
+            Category::Special => "Special line",
+        }
+    }
+
+    pub(crate) fn color(self) -> Color32 {
+        match self {
+            Category::Tram => Color32::from_rgb(200, 30, 45),
+            Category::Bus => Color32::from_rgb(20, 90, 180),
+            Category::NightBus => Color32::from_rgb(30, 30, 70),
+            Category::Special => Color32::from_rgb(220, 120, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn category_is_guessed_from_line_name() {
+        assert_eq!(Category::guess("0L"), Category::Tram);
+        assert_eq!(Category::guess("33"), Category::Tram);
+        assert_eq!(Category::guess("145"), Category::Bus);
+        assert_eq!(Category::guess("D"), Category::Bus);
+        assert_eq!(Category::guess("245"), Category::NightBus);
+        assert_eq!(Category::guess("Z10"), Category::Special);
+    }
+
+    #[test]
+    fn category_follows_gtfs_route_type() {
+        assert_eq!(Category::from_route_type(0), Some(Category::Tram));
+        assert_eq!(Category::from_route_type(3), Some(Category::Bus));
+        assert_eq!(Category::from_route_type(705), Some(Category::NightBus));
+        assert_eq!(Category::from_route_type(714), Some(Category::Special));
+        assert_eq!(Category::from_route_type(2), None);
+    }
+
+    #[test]
+    fn buses_of_the_timetable_are_refined_by_line_name() {
+        assert_eq!(Category::of("245", 3), Category::NightBus);
+        assert_eq!(Category::of("Z10", 3), Category::Special);
+        assert_eq!(Category::of("145", 3), Category::Bus);
+        // Suburban buses numbered like trams.
+        assert_eq!(Category::of("33", 3), Category::Bus);
+        assert_eq!(Category::of("Z1", 0), Category::Tram);
+        assert_eq!(Category::of("145", 705), Category::NightBus);
+        assert_eq!(Category::of("245", 2), Category::NightBus);
+    }
+}
@@ -0,0 +1,207 @@
+//! Matching vehicles to the trips of the timetable, to tell how late they are.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+
+use crate::{
+    geo,
+    gtfs::{Schedule, Trip},
+    vehicle::Vehicle,
+};
+
+/// Vehicles further than this from the stops of a trip, in meters, are not on it. Stops are
+/// joined with straight lines, so it has to allow for curves.
+const MAX_DISTANCE: f64 = 300.0;
+
+/// Trips are considered from a while before their departure, to include vehicles waiting at
+/// the terminus, and long after their arrival, to include late ones.
+const BEFORE_DEPARTURE: TimeDelta = TimeDelta::minutes(15);
+const AFTER_ARRIVAL: TimeDelta = TimeDelta::minutes(60);
+
+/// Vehicles are hardly ever more early than that.
+const MAX_EARLY: TimeDelta = TimeDelta::minutes(10);
+
+/// Previous trip of the vehicle is preferred by this many seconds, so matches do not flicker
+/// between trips which run close to each other.
+const STICKINESS: f64 = 120.0;
+
+/// Trip the vehicle is most likely on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TripMatch {
+    pub trip_id: Arc<str>,
+    /// Day the trip belongs to, which is the day before for trips after midnight.
+    pub service_date: NaiveDate,
+    /// Positive when the vehicle is late.
+    pub delay: TimeDelta,
+    /// Index, in the stop times of the trip, of the stop the vehicle is heading to.
+    pub next_stop: usize,
+}
+
+/// How late or early the vehicle is, in the terms riders use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Punctuality {
+    Early,
+    OnTime,
+    Late,
+}
+
+impl Punctuality {
+    /// Up to a minute early or three minutes late is on time.
+    pub fn of(delay: TimeDelta) -> Self {
+        if delay < TimeDelta::minutes(-1) {
+            Punctuality::Early
+        } else if delay > TimeDelta::minutes(3) {
+            Punctuality::Late
+        } else {
+            Punctuality::OnTime
+        }
+    }
+}
+
+/// Delay rounded to whole minutes, the way it is shown.
+pub fn minutes(delay: TimeDelta) -> i64 {
+    (delay.num_seconds() as f64 / 60.0).round() as i64
+}
+
+/// Brigades in the MPK feed might be zero-padded, or prefixed with the line.
+fn same_brigade(line: &str, feed: &str, timetable: &str) -> bool {
+    let timetable = timetable.trim_start_matches('0');
+    let feed = feed.trim_start_matches('0');
+    feed == timetable
+        || feed
+            .strip_prefix(line)
+            .is_some_and(|brigade| brigade.trim_start_matches('0') == timetable)
+}
+
+/// Find the trip of the timetable the vehicle is on, by its line, brigade if known, and where
+/// it is at what time.
+pub(crate) fn estimate(schedule: &Schedule, vehicle: &Vehicle) -> Option<TripMatch> {
+    let route = schedule.route_by_name(&vehicle.line)?;
+    let time = vehicle.last_seen();
+    let position = vehicle.position();
+
+    let today = time.with_timezone(&schedule.timezone).date_naive();
+    let dates = [today, today.pred_opt()?];
+
+    let mut trips: Vec<&Trip> = schedule.trips_of(&route.id).collect();
+    if let Some(brigade) = &vehicle.brigade {
+        let of_brigade = |trip: &&Trip| {
+            trip.brigade
+                .as_ref()
+                .is_some_and(|other| same_brigade(&vehicle.line, brigade, other))
+        };
+        // Brigades might be numbered differently than the timetable expects.
+        if trips.iter().any(of_brigade) {
+            trips.retain(of_brigade);
+        }
+    }
+
+    let previous = vehicle.trip().map(|trip| &trip.trip_id);
+
+    trips
+        .into_iter()
+        .flat_map(|trip| dates.map(|date| (trip, date)))
+        .filter(|(trip, date)| schedule.runs_on(&trip.service_id, *date))
+        .filter_map(|(trip, date)| {
+            let (candidate, distance) = locate(schedule, trip, date, position, time)?;
+            let mut score = candidate.delay.num_seconds().abs() as f64 + distance;
+            if previous == Some(&trip.id) {
+                score -= STICKINESS;
+            }
+            Some((candidate, score))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(candidate, _)| candidate)
+}
+
+/// Where along the trip the vehicle is, and how far from its stops, if it can be on it at all.
+fn locate(
+    schedule: &Schedule,
+    trip: &Trip,
+    date: NaiveDate,
+    position: walkers::Position,
+    time: DateTime<Utc>,
+) -> Option<(TripMatch, f64)> {
+    let stop_times = schedule.stop_times(&trip.id);
+    let first = schedule.time(date, stop_times.first()?.departure);
+    let last = schedule.time(date, stop_times.last()?.arrival);
+    if time < first - BEFORE_DEPARTURE || time > last + AFTER_ARRIVAL {
+        return None;
+    }
+
+    let stops: Vec<_> = stop_times
+        .iter()
+        .map(|stop_time| Some(schedule.stops.get(&stop_time.stop_id)?.position))
+        .collect::<Option<_>>()?;
+    let (n, t, distance) = geo::closest_segment(&stops, position)?;
+    if distance > MAX_DISTANCE {
+        return None;
+    }
+
+    let departure = stop_times[n].departure as f64;
+    let arrival = stop_times[n + 1].arrival as f64;
+    let scheduled = schedule.time(date, (departure + (arrival - departure) * t).round() as u32);
+    let delay = time - scheduled;
+    if delay < -MAX_EARLY || delay > AFTER_ARRIVAL {
+        return None;
+    }
+
+    Some((
+        TripMatch {
+            trip_id: trip.id.clone(),
+            service_date: date,
+            delay,
+            next_stop: n + 1,
+        },
+        distance,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone as _;
+    use chrono_tz::Europe::Warsaw;
+
+    use super::*;
+    use crate::{testing, vehicle::Fix};
+
+    #[test]
+    fn punctuality_tolerates_small_delays() {
+        assert_eq!(Punctuality::of(TimeDelta::seconds(-90)), Punctuality::Early);
+        assert_eq!(Punctuality::of(TimeDelta::minutes(-1)), Punctuality::OnTime);
+        assert_eq!(Punctuality::of(TimeDelta::minutes(3)), Punctuality::OnTime);
+        assert_eq!(Punctuality::of(TimeDelta::seconds(181)), Punctuality::Late);
+    }
+
+    #[test]
+    fn delay_is_rounded_to_minutes() {
+        assert_eq!(minutes(TimeDelta::seconds(89)), 1);
+        assert_eq!(minutes(TimeDelta::seconds(90)), 2);
+        assert_eq!(minutes(TimeDelta::seconds(-29)), 0);
+    }
+
+    #[test]
+    fn scheduled_time_is_rounded_to_seconds() {
+        // Between the second and the third stop of trip 3_1, which is due there at 8:07:44.8.
+        let now = Warsaw
+            .with_ymd_and_hms(2026, 10, 19, 8, 10, 45)
+            .unwrap()
+            .with_timezone(&Utc);
+        let t = 134.8 / 270.0;
+        let mut vehicle = Vehicle::new("1".to_string());
+        vehicle.update(
+            "1",
+            Fix {
+                time: now,
+                position: walkers::lat_lon(51.1080 + 0.004 * t, 17.0400 + 0.02 * t),
+                snapped: None,
+            },
+        );
+
+        let trip = estimate(&testing::schedule(), &vehicle).expect("vehicle is matched");
+
+        assert_eq!(&*trip.trip_id, "3_1");
+        assert_eq!(trip.delay, TimeDelta::minutes(3));
+    }
+}
@@ -0,0 +1,194 @@
+//! Departures from a stop, as planned in the timetable and adjusted by the live delays.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    gtfs::Schedule,
+    vehicle::{Vehicle, VehicleKey},
+};
+
+/// Departures which were due that long ago are still shown, since they might be late.
+const LOOK_BACK: TimeDelta = TimeDelta::minutes(30);
+
+/// How far ahead departures are shown.
+const LOOK_AHEAD: TimeDelta = TimeDelta::hours(2);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Departure {
+    pub line: String,
+    pub headsign: String,
+    pub scheduled: DateTime<Utc>,
+    /// Scheduled time adjusted by the delay of the vehicle serving the trip, if it is known.
+    pub expected: DateTime<Utc>,
+    /// Vehicle expected to serve the departure, if any was matched to the trip.
+    pub vehicle: Option<VehicleKey>,
+}
+
+impl Departure {
+    /// Whether the expected time comes from a live vehicle, rather than just the timetable.
+    pub fn live(&self) -> bool {
+        self.vehicle.is_some()
+    }
+}
+
+/// Upcoming departures from the stop, soonest first, at most `per_line` of each line.
+pub fn departures(
+    schedule: &Schedule,
+    stop_id: &str,
+    vehicles: &HashMap<VehicleKey, Vehicle>,
+    now: DateTime<Utc>,
+    per_line: usize,
+) -> Vec<Departure> {
+    let today = now.with_timezone(&schedule.timezone).date_naive();
+    let dates = [today, today.pred_opt().unwrap_or(today)];
+
+    let mut departures: Vec<Departure> = schedule
+        .calls_at(stop_id)
+        .flat_map(|call| dates.map(|date| (call, date)))
+        .filter_map(|((trip, n, stop_time), date)| {
+            // Trips ending here do not depart.
+            if n + 1 == schedule.stop_times(&trip.id).len()
+                || !schedule.runs_on(&trip.service_id, date)
+            {
+                return None;
+            }
+
+            let scheduled = schedule.time(date, stop_time.departure);
+            if scheduled < now - LOOK_BACK || scheduled > now + LOOK_AHEAD {
+                return None;
+            }
+
+            let serving = vehicles.iter().find_map(|(key, vehicle)| {
+                let matched = vehicle.trip()?;
+                (matched.trip_id == trip.id && matched.service_date == date)
+                    .then_some((key, vehicle, matched))
+            });
+
+            let (expected, vehicle) = match serving {
+                // Already gone.
+                Some((_, _, matched)) if matched.next_stop > n => return None,
+                Some((key, _, matched)) => (scheduled + matched.delay, Some(key.clone())),
+                None => (scheduled, None),
+            };
+            if expected < now {
+                return None;
+            }
+
+            Some(Departure {
+                line: schedule
+                    .routes
+                    .get(&trip.route_id)
+                    .map_or_else(|| trip.route_id.clone(), |route| route.short_name.clone()),
+                headsign: trip.headsign.clone().unwrap_or_default(),
+                scheduled,
+                expected,
+                vehicle,
+            })
+        })
+        .collect();
+
+    departures.sort_by_key(|departure| departure.expected);
+
+    let mut count: HashMap<String, usize> = HashMap::new();
+    departures.retain(|departure| {
+        let count = count.entry(departure.line.clone()).or_default();
+        *count += 1;
+        *count <= per_line
+    });
+    departures
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone as _;
+    use chrono_tz::Europe::Warsaw;
+
+    use super::*;
+    use crate::{delay, testing, vehicle::Fix};
+
+    fn key(id: &str) -> VehicleKey {
+        VehicleKey {
+            feed: 0,
+            id: id.to_string(),
+        }
+    }
+
+    /// Vehicle of line 1 seen at the position, matched to the timetable the way feeds do it.
+    fn vehicle(schedule: &Schedule, latitude: f64, longitude: f64, time: DateTime<Utc>) -> Vehicle {
+        let position = walkers::lat_lon(latitude, longitude);
+        let mut vehicle = Vehicle::new("1".to_string());
+        vehicle.brigade = Some("00101".to_string());
+        vehicle.update(
+            "1",
+            Fix {
+                time,
+                position,
+                snapped: schedule.snap("1", position, 30.0),
+            },
+        );
+        vehicle.set_trip(delay::estimate(schedule, &vehicle));
+        vehicle
+    }
+
+    #[test]
+    fn departures_follow_the_timetable() {
+        // Monday morning.
+        let now = Warsaw
+            .with_ymd_and_hms(2026, 10, 19, 8, 3, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let departures = departures(&testing::schedule(), "2", &HashMap::new(), now, 3);
+
+        let lines: Vec<_> = departures
+            .iter()
+            .map(|departure| (departure.line.as_str(), departure.headsign.as_str()))
+            .collect();
+        assert_eq!(lines, vec![("1", "Plac Grunwaldzki"), ("145", "Kozanów")]);
+        assert!(departures.iter().all(|departure| !departure.live()));
+        assert_eq!(
+            departures[0].expected,
+            Warsaw.with_ymd_and_hms(2026, 10, 19, 8, 5, 30).unwrap()
+        );
+    }
+
+    #[test]
+    fn departures_are_adjusted_by_live_delay() {
+        // Half way between the first and the second stop, due there at 8:02:30.
+        let now = Warsaw
+            .with_ymd_and_hms(2026, 10, 19, 8, 4, 30)
+            .unwrap()
+            .with_timezone(&Utc);
+        let schedule = testing::schedule();
+        let vehicles = HashMap::from([(key("2401"), vehicle(&schedule, 51.1035, 17.0380, now))]);
+
+        let departures = departures(&schedule, "2", &vehicles, now, 3);
+
+        let departure = &departures[0];
+        assert_eq!(departure.line, "1");
+        assert_eq!(departure.vehicle, Some(key("2401")));
+        assert_eq!(
+            departure.expected - departure.scheduled,
+            TimeDelta::minutes(2)
+        );
+    }
+
+    #[test]
+    fn departed_trips_are_not_shown() {
+        // Past the second stop already, ahead of the timetable, so its departure is still due.
+        let now = Warsaw
+            .with_ymd_and_hms(2026, 10, 19, 8, 5, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let schedule = testing::schedule();
+        let vehicle = vehicle(&schedule, 51.1100, 17.0500, now);
+        assert!(vehicle.trip().is_some());
+        let vehicles = HashMap::from([(key("2401"), vehicle)]);
+
+        let departures = departures(&schedule, "2", &vehicles, now, 3);
+
+        assert!(departures.iter().all(|departure| departure.line != "1"));
+    }
+}
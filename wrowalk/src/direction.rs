@@ -0,0 +1,76 @@
+//! Where the vehicles are heading to, since the feed does not tell.
+
+use std::sync::Arc;
+
+use walkers::Position;
+
+use crate::{geo, gtfs::Schedule, vehicle::Vehicle};
+
+/// Positions further than this from a shape, in meters, are not on it.
+const MAX_DISTANCE: f64 = 50.0;
+
+/// Vehicle has to move at least that far along a shape, in meters, to tell which way it goes.
+const MIN_PROGRESS: f64 = 10.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Direction {
+    /// Destination, as shown on the vehicle.
+    pub headsign: String,
+    /// Stop the vehicle is heading to, if it is known.
+    pub next_stop: Option<Arc<str>>,
+}
+
+/// Direction of the vehicle, taken from the trip it was matched to, or inferred from which
+/// way it moves along the shapes of its line.
+pub(crate) fn infer(schedule: &Schedule, vehicle: &Vehicle) -> Option<Direction> {
+    if let Some(matched) = vehicle.trip() {
+        let trip = schedule.trips.get(&*matched.trip_id)?;
+        return Some(Direction {
+            headsign: trip.headsign.clone().unwrap_or_default(),
+            next_stop: schedule
+                .stop_times(&trip.id)
+                .get(matched.next_stop)
+                .map(|stop_time| stop_time.stop_id.clone()),
+        });
+    }
+
+    let positions = vehicle.positions();
+    let (first, last) = (*positions.first()?, *positions.last()?);
+    let route = schedule.route_by_name(&vehicle.line)?;
+
+    let (trip, shape, along, _) = schedule
+        .shapes_of(&route.id)
+        .into_iter()
+        .filter_map(|(trip, shape)| {
+            let (from, from_distance) = geo::along_polyline(shape, first)?;
+            let (to, to_distance) = geo::along_polyline(shape, last)?;
+            (from_distance.max(to_distance) <= MAX_DISTANCE && to - from >= MIN_PROGRESS)
+                .then_some((trip, shape, to, from_distance + to_distance))
+        })
+        .min_by(|a, b| a.3.total_cmp(&b.3))?;
+
+    Some(Direction {
+        headsign: trip.headsign.clone().unwrap_or_default(),
+        next_stop: next_stop(schedule, &trip.id, shape, along),
+    })
+}
+
+/// First stop of the trip which is further along the shape than the vehicle.
+fn next_stop(
+    schedule: &Schedule,
+    trip_id: &str,
+    shape: &[Position],
+    along: f64,
+) -> Option<Arc<str>> {
+    schedule
+        .stop_times(trip_id)
+        .iter()
+        .find(|stop_time| {
+            schedule
+                .stops
+                .get(&stop_time.stop_id)
+                .and_then(|stop| geo::along_polyline(shape, stop.position))
+                .is_some_and(|(stop_along, _)| stop_along > along)
+        })
+        .map(|stop_time| stop_time.stop_id.clone())
+}
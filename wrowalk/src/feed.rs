@@ -0,0 +1,316 @@
+//! Common interface of the sources of live vehicle positions.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use walkers::Position;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    delay, direction,
+    gtfs::Schedule,
+    http::Fetched,
+    polling::{self, PollingPolicy},
+    vehicle::{Fix, Vehicle},
+};
+
+static APP_IN_BACKGROUND: OnceLock<AtomicBool> = OnceLock::new();
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_podusowski_wrowalk_MainActivity_setAppInBackground(
+    _env: jni::JNIEnv,
+    _class: jni::objects::JClass,
+    is_background: bool,
+) {
+    set_app_in_background(is_background);
+}
+
+/// Feeds stop polling and forget their vehicles while the app is in background.
+pub fn set_app_in_background(is_background: bool) {
+    let atomic = APP_IN_BACKGROUND.get_or_init(|| AtomicBool::new(false));
+    atomic.store(is_background, Ordering::SeqCst);
+}
+
+pub fn is_app_in_background() -> bool {
+    APP_IN_BACKGROUND
+        .get()
+        .map(|a| a.load(Ordering::SeqCst))
+        .unwrap_or(false)
+}
+
+/// Source of live vehicle positions, e.g. a single public transport operator.
+pub trait VehicleFeed {
+    /// Human readable name of the feed.
+    fn name(&self) -> &str;
+
+    /// Start fetching vehicles in the background. Calling it again has no effect.
+    fn start(&mut self, egui_ctx: egui::Context);
+
+    /// Snapshot of the vehicles which are currently tracked.
+    fn vehicles(&self) -> HashMap<String, Vehicle>;
+
+    /// Health of the feed.
+    fn status(&self) -> FeedStatus;
+
+    fn settings(&self) -> FeedSettings;
+
+    fn set_settings(&mut self, settings: FeedSettings);
+
+    /// Timetable to snap the positions onto the routes with. Snapshots of the timetable
+    /// which is still loading can be given, and replaced later.
+    fn set_schedule(&mut self, schedule: Arc<Schedule>);
+}
+
+/// Knobs controlling how a feed tracks its vehicles.
+#[derive(Debug, Clone)]
+pub struct FeedSettings {
+    /// Vehicles which did not report their position for this long are forgotten.
+    pub evict_after: TimeDelta,
+    pub polling: PollingPolicy,
+    /// Positions within this many meters from the route of the line are snapped onto it.
+    pub snap_tolerance: f64,
+}
+
+impl Default for FeedSettings {
+    fn default() -> Self {
+        Self {
+            evict_after: TimeDelta::minutes(15),
+            polling: PollingPolicy::default(),
+            snap_tolerance: 30.0,
+        }
+    }
+}
+
+/// Reasons why fetching vehicles might fail.
+#[derive(Debug, thiserror::Error)]
+pub enum FeedError {
+    #[error("could not reach the server: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("server responded with {status}")]
+    Status {
+        status: reqwest::StatusCode,
+        /// How long the server asked us to wait before trying again.
+        retry_after: Option<Duration>,
+    },
+    #[error("could not decode CSV: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("could not decode protobuf: {0}")]
+    Protobuf(#[from] prost::DecodeError),
+    #[error("unexpected data layout: {0}")]
+    Schema(String),
+    #[error("could not read the recording: {0}")]
+    Recording(#[from] std::io::Error),
+}
+
+impl FeedError {
+    /// Error for a response which came back with a non-success status.
+    pub(crate) fn status(response: &reqwest::Response) -> Self {
+        Self::Status {
+            status: response.status(),
+            retry_after: polling::retry_after(response.headers(), Utc::now()),
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Status { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// Health of the feed, as seen by the last few fetches.
+#[derive(Debug, Clone, Default)]
+pub struct FeedStatus {
+    /// When vehicles were successfully fetched for the last time.
+    pub last_success: Option<DateTime<Utc>>,
+    /// Error from the last fetch, cleared once a fetch succeeds again.
+    pub last_error: Option<Arc<FeedError>>,
+    /// How many fetches in a row have failed.
+    pub consecutive_failures: u32,
+    pub polls: PollCounts,
+}
+
+/// How the polls turned out so far.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PollCounts {
+    /// Polls which brought new data.
+    pub new: u64,
+    /// Polls which succeeded, but the data was the same as the last time.
+    pub unchanged: u64,
+    pub failed: u64,
+}
+
+/// Position of a single vehicle, as reported by a feed.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Identifier of the vehicle, unique within the feed.
+    pub id: String,
+    pub line: String,
+    pub brigade: Option<String>,
+    /// Registration plate, which only buses have.
+    pub registration: Option<String>,
+    pub position: Position,
+    /// When the vehicle was at `position`.
+    pub time: DateTime<Utc>,
+}
+
+/// State shared between a feed and its background task.
+pub(crate) struct Shared {
+    vehicles: Mutex<HashMap<String, Vehicle>>,
+    status: Mutex<FeedStatus>,
+    settings: Mutex<FeedSettings>,
+    schedule: Mutex<Arc<Schedule>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+}
+
+impl Shared {
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            vehicles: Default::default(),
+            status: Default::default(),
+            settings: Default::default(),
+            schedule: Default::default(),
+            clock,
+        }
+    }
+
+    /// Fresh state with the same settings and timetable, but a different clock.
+    pub fn replace_clock(&self, clock: Arc<dyn Clock>) -> Self {
+        let shared = Self::with_clock(clock);
+        shared.set_settings(self.settings());
+        shared.set_schedule(self.schedule());
+        shared
+    }
+
+    pub fn vehicles(&self) -> HashMap<String, Vehicle> {
+        self.vehicles.lock().unwrap().clone()
+    }
+
+    pub fn status(&self) -> FeedStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn settings(&self) -> FeedSettings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn set_settings(&self, settings: FeedSettings) {
+        *self.settings.lock().unwrap() = settings;
+    }
+
+    pub fn schedule(&self) -> Arc<Schedule> {
+        self.schedule.lock().unwrap().clone()
+    }
+
+    pub fn set_schedule(&self, schedule: Arc<Schedule>) {
+        *self.schedule.lock().unwrap() = schedule;
+    }
+
+    /// Update vehicles with the reports, if there are any, and forget ones which went silent,
+    /// most likely to the depot. Matching against the timetable takes a while, so it is done
+    /// on a copy, not to block the UI. Only the background task changes the vehicles,
+    /// therefore nothing is lost when the copy is put back.
+    fn apply(&self, reports: &[Report]) {
+        let settings = self.settings();
+        let schedule = self.schedule();
+        let mut vehicles = self.vehicles();
+
+        for report in reports {
+            let vehicle = vehicles
+                .entry(report.id.clone())
+                .or_insert_with(|| Vehicle::new(report.line.clone()));
+            vehicle.update(
+                &report.line,
+                Fix {
+                    time: report.time,
+                    position: report.position,
+                    snapped: schedule.snap(&report.line, report.position, settings.snap_tolerance),
+                },
+            );
+            vehicle.brigade = report.brigade.clone();
+            vehicle.registration = report.registration.clone();
+            vehicle.set_trip(delay::estimate(&schedule, vehicle));
+            vehicle.set_direction(direction::infer(&schedule, vehicle));
+        }
+
+        let now = self.clock.now();
+        vehicles.retain(|_, vehicle| vehicle.age(now) < settings.evict_after);
+
+        log::debug!("Vehicles: {vehicles:#?}");
+        *self.vehicles.lock().unwrap() = vehicles;
+    }
+}
+
+/// Keep calling `fetch` and feeding its results into `shared`, until the runtime is dropped.
+/// The argument of `fetch` tells whether vehicles were forgotten in the meantime, therefore
+/// the payload must be fetched in full, even if it did not change.
+pub(crate) async fn poll_continuously<F>(shared: Arc<Shared>, egui_ctx: egui::Context, mut fetch: F)
+where
+    F: AsyncFnMut(bool) -> Result<Fetched<Vec<Report>>, FeedError>,
+{
+    let mut failures = 0;
+    let mut cleared = false;
+
+    loop {
+        let mut retry_after = None;
+
+        if !is_app_in_background() {
+            let result = fetch(std::mem::take(&mut cleared)).await;
+            match &result {
+                Ok(Fetched::New(reports)) => shared.apply(reports),
+                // Silent vehicles are forgotten even if the feed is down or did not change.
+                _ => shared.apply(&[]),
+            }
+            let mut status = shared.status.lock().unwrap();
+
+            match result {
+                Ok(fetched) => {
+                    if let Fetched::New(_) = fetched {
+                        status.polls.new += 1;
+                    } else {
+                        log::debug!("Nothing new in the feed.");
+                        status.polls.unchanged += 1;
+                    }
+
+                    failures = 0;
+                    status.last_success = Some(shared.clock.now());
+                    status.last_error = None;
+                }
+                Err(err) => {
+                    log::warn!("Could not fetch vehicles: {err}");
+                    failures += 1;
+                    retry_after = err.retry_after();
+                    status.last_error = Some(Arc::new(err));
+                    status.polls.failed += 1;
+                }
+            }
+
+            status.consecutive_failures = failures;
+            drop(status);
+
+            egui_ctx.request_repaint();
+        } else {
+            log::info!("App is in background, skipping fetch.");
+            shared.vehicles.lock().unwrap().clear();
+            cleared = true;
+        }
+
+        let delay = shared.settings().polling.delay(failures, retry_after);
+        log::debug!("Next poll in {delay:?}.");
+        shared.clock.sleep(delay).await;
+    }
+}
@@ -0,0 +1,101 @@
+//! Which lines are shown on the map, since all of them at once are too many for the city
+//! centre.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+/// Lines to show. All of them by default, including ones which show up later.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LineFilter {
+    /// Lines hidden by the user. Lines which are not listed here are shown, so lines which
+    /// do not run at the moment, e.g. night ones, show up once they do.
+    hidden: BTreeSet<String>,
+}
+
+impl LineFilter {
+    pub fn shows(&self, line: &str) -> bool {
+        !self.hidden.contains(line)
+    }
+
+    /// Whether any line is hidden.
+    pub fn is_active(&self) -> bool {
+        !self.hidden.is_empty()
+    }
+
+    /// Lines hidden by the user, running or not.
+    pub fn hidden(&self) -> impl Iterator<Item = &str> {
+        self.hidden.iter().map(String::as_str)
+    }
+
+    /// Show or hide one line.
+    pub fn set(&mut self, line: &str, show: bool) {
+        if show {
+            self.hidden.remove(line);
+        } else {
+            self.hidden.insert(line.to_string());
+        }
+    }
+
+    /// Show this line and hide all other `seen` ones.
+    pub fn only<'a>(&mut self, line: &str, seen: impl IntoIterator<Item = &'a str>) {
+        self.none(seen);
+        self.hidden.remove(line);
+    }
+
+    pub fn all(&mut self) {
+        self.hidden.clear();
+    }
+
+    /// Hide all `seen` lines.
+    pub fn none<'a>(&mut self, seen: impl IntoIterator<Item = &'a str>) {
+        self.hidden.extend(seen.into_iter().map(String::from));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_lines_are_shown_by_default() {
+        let filter = LineFilter::default();
+        assert!(filter.shows("1"));
+        assert!(filter.shows("145"));
+        assert!(!filter.is_active());
+    }
+
+    #[test]
+    fn hidden_lines_stay_hidden_and_new_ones_show() {
+        let mut filter = LineFilter::default();
+        filter.set("1", false);
+        assert!(!filter.shows("1"));
+        assert!(filter.shows("33"));
+        assert!(filter.shows("D"));
+        assert!(filter.is_active());
+
+        filter.set("1", true);
+        assert!(filter.shows("1"));
+        assert!(!filter.is_active());
+    }
+
+    #[test]
+    fn only_all_and_none() {
+        let seen = ["1", "33", "145"];
+        let mut filter = LineFilter::default();
+        filter.only("33", seen);
+        assert!(filter.shows("33"));
+        assert!(!filter.shows("1"));
+        // Night line, which was not running yet.
+        assert!(filter.shows("206"));
+
+        filter.none(seen);
+        assert!(!filter.shows("33"));
+        assert_eq!(filter.hidden().collect::<Vec<_>>(), ["1", "145", "33"]);
+
+        filter.all();
+        assert!(filter.shows("1"));
+        assert!(filter.shows("D"));
+    }
+}
@@ -0,0 +1,133 @@
+//! Geometry on the surface of the Earth, good enough for distances within a city.
+
+use walkers::Position;
+
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// Distance between two positions, in meters.
+pub(crate) fn distance(a: Position, b: Position) -> f64 {
+    let x = (b.x() - a.x()).to_radians() * ((a.y() + b.y()) / 2.0).to_radians().cos();
+    let y = (b.y() - a.y()).to_radians();
+    x.hypot(y) * EARTH_RADIUS
+}
+
+/// Direction from one position to another, in degrees clockwise from the north.
+pub(crate) fn bearing(from: Position, to: Position) -> f64 {
+    let x = (to.x() - from.x()).to_radians() * ((from.y() + to.y()) / 2.0).to_radians().cos();
+    let y = (to.y() - from.y()).to_radians();
+    x.atan2(y).to_degrees().rem_euclid(360.0)
+}
+
+/// Position `t` of the way from `a` to `b`.
+pub(crate) fn lerp(a: Position, b: Position, t: f64) -> Position {
+    walkers::lat_lon(a.y() + (b.y() - a.y()) * t, a.x() + (b.x() - a.x()) * t)
+}
+
+/// Position `meters` away from `from`, in the direction of `bearing`.
+pub(crate) fn advance(from: Position, bearing: f64, meters: f64) -> Position {
+    let bearing = bearing.to_radians();
+    let north = meters * bearing.cos() / EARTH_RADIUS;
+    let east = meters * bearing.sin() / (EARTH_RADIUS * from.y().to_radians().cos());
+    walkers::lat_lon(from.y() + north.to_degrees(), from.x() + east.to_degrees())
+}
+
+/// Segment of the polyline closest to the position: its index, how far along it the closest
+/// point is (from 0.0 to 1.0), and how far that point is, in meters.
+pub(crate) fn closest_segment(
+    polyline: &[Position],
+    position: Position,
+) -> Option<(usize, f64, f64)> {
+    // Flat projection around the position, in meters, is accurate enough at this scale.
+    let scale = position.y().to_radians().cos();
+    let flat = |point: Position| {
+        (
+            (point.x() - position.x()).to_radians() * scale * EARTH_RADIUS,
+            (point.y() - position.y()).to_radians() * EARTH_RADIUS,
+        )
+    };
+
+    polyline
+        .windows(2)
+        .enumerate()
+        .map(|(n, segment)| {
+            let (ax, ay) = flat(segment[0]);
+            let (bx, by) = flat(segment[1]);
+            let (dx, dy) = (bx - ax, by - ay);
+            let length = dx * dx + dy * dy;
+            let t = if length > 0.0 {
+                (-(ax * dx + ay * dy) / length).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            (n, t, (ax + dx * t).hypot(ay + dy * t))
+        })
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+}
+
+/// Point of the polyline closest to the position, and how far it is, in meters.
+pub(crate) fn closest_on_polyline(
+    polyline: &[Position],
+    position: Position,
+) -> Option<(Position, f64)> {
+    let (n, t, distance) = closest_segment(polyline, position)?;
+    Some((lerp(polyline[n], polyline[n + 1], t), distance))
+}
+
+/// How far along the polyline, in meters, the point closest to the position is, and how far
+/// that point is from the position.
+pub(crate) fn along_polyline(polyline: &[Position], position: Position) -> Option<(f64, f64)> {
+    let (n, t, distance) = closest_segment(polyline, position)?;
+    let before: f64 = polyline[..=n]
+        .windows(2)
+        .map(|segment| self::distance(segment[0], segment[1]))
+        .sum();
+    Some((
+        before + t * self::distance(polyline[n], polyline[n + 1]),
+        distance,
+    ))
+}
+
+/// Position `meters` further along the polyline from the point closest to `from`, going the
+/// way which is closer to `bearing`. It stops at the end of the polyline, rather than going
+/// off it.
+pub(crate) fn follow_polyline(
+    polyline: &[Position],
+    from: Position,
+    bearing: f64,
+    meters: f64,
+) -> Option<Position> {
+    let (n, t, _) = closest_segment(polyline, from)?;
+    let start = lerp(polyline[n], polyline[n + 1], t);
+
+    let turn = (self::bearing(polyline[n], polyline[n + 1]) - bearing).rem_euclid(360.0);
+    let ahead: Vec<Position> = if turn.min(360.0 - turn) <= 90.0 {
+        std::iter::once(start)
+            .chain(polyline[n + 1..].iter().copied())
+            .collect()
+    } else {
+        std::iter::once(start)
+            .chain(polyline[..=n].iter().rev().copied())
+            .collect()
+    };
+
+    let mut left = meters;
+    for segment in ahead.windows(2) {
+        let length = distance(segment[0], segment[1]);
+        if length >= left {
+            return Some(lerp(segment[0], segment[1], left / length));
+        }
+        left -= length;
+    }
+    ahead.last().copied()
+}
+
+/// Middle of the bounding box of the positions.
+pub(crate) fn center(positions: &[Position]) -> Option<Position> {
+    let first = positions.first()?;
+    let (mut min, mut max) = ((first.x(), first.y()), (first.x(), first.y()));
+    for position in positions {
+        min = (min.0.min(position.x()), min.1.min(position.y()));
+        max = (max.0.max(position.x()), max.1.max(position.y()));
+    }
+    Some(walkers::lon_lat((min.0 + max.0) / 2., (min.1 + max.1) / 2.))
+}
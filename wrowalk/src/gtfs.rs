@@ -0,0 +1,964 @@
+//! Static timetable published as GTFS, which Wrocław does too.
+//! https://gtfs.org/documentation/schedule/reference/
+//!
+//! The feed is loaded in the background, file by file, and a snapshot of the [`Schedule`] is
+//! published after each of them, so stops can be shown long before all the stop times are read.
+
+use chrono::{DateTime, Datelike as _, NaiveDate, NaiveTime, TimeZone as _, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    future::Future as _,
+    hash::{DefaultHasher, Hash as _, Hasher as _},
+    io::Read as _,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use walkers::Position;
+
+use crate::{category::Category, geo};
+
+type Archive<'a> = zip::ZipArchive<std::io::Cursor<&'a [u8]>>;
+
+/// Timetable of MPK Wrocław, published on the Open Data portal.
+const URL: &str = "https://www.wroclaw.pl/open-data/87b09b32-f076-4475-8ec9-6020ed1f9ac0/OtwartyWroclaw_rozklad_jazdy_GTFS.zip";
+
+/// Files of the feed, in the order they are loaded. Stops come first, since they are useful
+/// on their own, and stop times last, since they take the longest.
+const FILES: [&str; 8] = [
+    "agency.txt",
+    "stops.txt",
+    "routes.txt",
+    "calendar.txt",
+    "calendar_dates.txt",
+    "trips.txt",
+    "shapes.txt",
+    "stop_times.txt",
+];
+
+/// Records parsed between the pauses while loading, see [`Schedule::load`].
+const CHUNK: usize = 10_000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GtfsError {
+    #[error("could not reach the server: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("server responded with {0}")]
+    Status(reqwest::StatusCode),
+    #[error("could not read the timetable: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not unpack the timetable: {0}")]
+    Archive(#[from] zip::result::ZipError),
+    #[error("could not decode {0}: {1}")]
+    Csv(&'static str, csv::Error),
+    #[error("timetable has no {0}")]
+    MissingFile(&'static str),
+}
+
+/// Where the timetable comes from.
+#[derive(Debug, Clone)]
+pub enum Source {
+    File(PathBuf),
+    /// Downloaded file is cached, and downloaded again only if it changed.
+    Url(String),
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Source::Url(URL.to_string())
+    }
+}
+
+impl Source {
+    /// URL if it looks like one, a path otherwise.
+    pub fn parse(source: &str) -> Self {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            Source::Url(source.to_string())
+        } else {
+            Source::File(source.into())
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Stop {
+    pub id: Arc<str>,
+    /// Short code, printed on the stop sign.
+    pub code: Option<String>,
+    pub name: String,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub id: String,
+    /// Name of the line, as in the feeds, e.g. "33".
+    pub short_name: String,
+    pub long_name: String,
+    pub route_type: u32,
+}
+
+impl Route {
+    pub fn category(&self) -> Category {
+        Category::of(&self.short_name, self.route_type)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Trip {
+    pub id: Arc<str>,
+    pub route_id: String,
+    pub service_id: String,
+    pub headsign: Option<String>,
+    pub direction: Option<u8>,
+    pub shape_id: Option<String>,
+    /// Brigade serving the trip, a Wrocław extension of GTFS.
+    pub brigade: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StopTime {
+    /// Shared with [`Stop::id`], since there are millions of stop times.
+    pub stop_id: Arc<str>,
+    /// Seconds since the start of the service day, might be over 24 hours.
+    pub arrival: u32,
+    pub departure: u32,
+}
+
+/// Days of the week a service runs on, within a period.
+#[derive(Debug, Clone)]
+struct Service {
+    weekdays: [bool; 7],
+    start: NaiveDate,
+    end: NaiveDate,
+}
+
+/// Timetable, indexed for lookups. Ids of stops and trips are kept once, and shared by the
+/// indexes.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    pub timezone: Tz,
+    pub stops: HashMap<Arc<str>, Stop>,
+    pub routes: HashMap<String, Route>,
+    pub trips: HashMap<Arc<str>, Trip>,
+    /// Polylines the trips follow.
+    pub shapes: HashMap<String, Vec<Position>>,
+    /// Stop times of each trip, in order.
+    stop_times: HashMap<Arc<str>, Vec<StopTime>>,
+    services: HashMap<String, Service>,
+    /// Services added (true) or removed (false) on particular dates.
+    exceptions: HashMap<(String, NaiveDate), bool>,
+    routes_by_name: HashMap<String, String>,
+    trips_by_route: HashMap<String, Vec<Arc<str>>>,
+    /// Trips calling at each stop, with the index of the call in their stop times.
+    calls: HashMap<Arc<str>, Vec<(Arc<str>, usize)>>,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            timezone: chrono_tz::Europe::Warsaw,
+            stops: Default::default(),
+            routes: Default::default(),
+            trips: Default::default(),
+            shapes: Default::default(),
+            stop_times: Default::default(),
+            services: Default::default(),
+            exceptions: Default::default(),
+            routes_by_name: Default::default(),
+            trips_by_route: Default::default(),
+            calls: Default::default(),
+        }
+    }
+}
+
+impl Schedule {
+    /// Read the whole feed at once.
+    pub fn parse(zip: &[u8]) -> Result<Self, GtfsError> {
+        let mut archive = Archive::new(std::io::Cursor::new(zip))?;
+        let mut schedule = Self::default();
+        for file in FILES {
+            // Pausing does nothing, so loading finishes in one go.
+            let loading = std::pin::pin!(schedule.load(&mut archive, file, async || {}));
+            let waker = std::task::Waker::noop();
+            let std::task::Poll::Ready(result) =
+                loading.poll(&mut std::task::Context::from_waker(waker))
+            else {
+                unreachable!("loading pauses only when `pause` does");
+            };
+            result?;
+        }
+        Ok(schedule)
+    }
+
+    /// Route of a line, by the name used by the feeds.
+    pub fn route_by_name(&self, line: &str) -> Option<&Route> {
+        self.routes.get(self.routes_by_name.get(line)?)
+    }
+
+    pub fn trips_of(&self, route_id: &str) -> impl Iterator<Item = &Trip> {
+        self.trips_by_route
+            .get(route_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|trip_id| self.trips.get(trip_id))
+    }
+
+    /// Distinct shapes the trips of the route follow, each with one of the trips following it.
+    pub fn shapes_of(&self, route_id: &str) -> Vec<(&Trip, &[Position])> {
+        let mut shapes: Vec<(&Trip, &[Position])> = Vec::new();
+        for trip in self.trips_of(route_id) {
+            let Some(shape_id) = &trip.shape_id else {
+                continue;
+            };
+            if shapes
+                .iter()
+                .any(|(other, _)| other.shape_id.as_ref() == Some(shape_id))
+            {
+                continue;
+            }
+            if let Some(shape) = self.shapes.get(shape_id) {
+                shapes.push((trip, shape));
+            }
+        }
+        shapes
+    }
+
+    /// Position moved onto the route of the line, unless it is further than `tolerance` meters
+    /// from it, which usually means a diversion.
+    pub fn snap(&self, line: &str, position: Position, tolerance: f64) -> Option<Position> {
+        let route = self.route_by_name(line)?;
+        self.shapes_of(&route.id)
+            .into_iter()
+            .filter_map(|(_, shape)| geo::closest_on_polyline(shape, position))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .filter(|(_, distance)| *distance <= tolerance)
+            .map(|(snapped, _)| snapped)
+    }
+
+    /// Position `meters` further along the route of the line, in the direction of `bearing`,
+    /// see [`geo::follow_polyline`].
+    pub fn follow(
+        &self,
+        line: &str,
+        from: Position,
+        bearing: f64,
+        meters: f64,
+    ) -> Option<Position> {
+        let route = self.route_by_name(line)?;
+        let (shape, _) = self
+            .shapes_of(&route.id)
+            .into_iter()
+            .filter_map(|(_, shape)| Some((shape, geo::closest_segment(shape, from)?.2)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+        geo::follow_polyline(shape, from, bearing, meters)
+    }
+
+    pub fn stop_times(&self, trip_id: &str) -> &[StopTime] {
+        self.stop_times.get(trip_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Trips calling at the stop, with the index of the call in their stop times, and the stop
+    /// time itself.
+    pub fn calls_at(&self, stop_id: &str) -> impl Iterator<Item = (&Trip, usize, &StopTime)> {
+        self.calls
+            .get(stop_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|(trip_id, n)| {
+                Some((
+                    self.trips.get(trip_id)?,
+                    *n,
+                    self.stop_times.get(trip_id)?.get(*n)?,
+                ))
+            })
+    }
+
+    /// Names of the lines calling at the stop, numbers first.
+    pub fn lines_at(&self, stop_id: &str) -> Vec<&str> {
+        let mut lines: Vec<&str> = self
+            .calls_at(stop_id)
+            .filter_map(|(trip, _, _)| Some(self.routes.get(&trip.route_id)?.short_name.as_str()))
+            .collect();
+        sort_lines(&mut lines);
+        lines.dedup();
+        lines
+    }
+
+    /// Does the service run on the given day.
+    pub fn runs_on(&self, service_id: &str, date: NaiveDate) -> bool {
+        if let Some(added) = self.exceptions.get(&(service_id.to_string(), date)) {
+            return *added;
+        }
+        self.services.get(service_id).is_some_and(|service| {
+            (service.start..=service.end).contains(&date)
+                && service.weekdays[date.weekday().num_days_from_monday() as usize]
+        })
+    }
+
+    /// Time of the day, in seconds from the start of the service day, as a point in time.
+    /// Service day starts at noon minus 12 hours, which is not always midnight when the clocks
+    /// change.
+    pub fn time(&self, date: NaiveDate, seconds: u32) -> DateTime<Utc> {
+        let noon = date.and_time(NaiveTime::from_hms_opt(12, 0, 0).unwrap());
+        let noon = self
+            .timezone
+            .from_local_datetime(&noon)
+            .earliest()
+            .map_or_else(|| noon.and_utc(), |noon| noon.with_timezone(&Utc));
+        noon - chrono::TimeDelta::hours(12) + chrono::TimeDelta::seconds(seconds.into())
+    }
+
+    /// Load one file of the feed, awaiting `pause` every [`CHUNK`] records, so that large files
+    /// can be parsed without blocking the thread for long.
+    async fn load(
+        &mut self,
+        archive: &mut Archive<'_>,
+        file: &'static str,
+        pause: impl AsyncFn(),
+    ) -> Result<(), GtfsError> {
+        let Some(contents) = read(archive, file)? else {
+            return match file {
+                "stops.txt" | "routes.txt" | "trips.txt" | "stop_times.txt" => {
+                    Err(GtfsError::MissingFile(file))
+                }
+                _ => Ok(()),
+            };
+        };
+
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(contents.strip_prefix(b"\xef\xbb\xbf").unwrap_or(&contents));
+
+        macro_rules! records {
+            ($record:ty) => {
+                reader
+                    .deserialize::<$record>()
+                    .map(|record| record.map_err(|err| GtfsError::Csv(file, err)))
+            };
+        }
+
+        let mut parsed = 0;
+        macro_rules! parsed {
+            () => {
+                parsed += 1;
+                if parsed % CHUNK == 0 {
+                    pause().await;
+                }
+            };
+        }
+
+        match file {
+            "agency.txt" => {
+                for agency in records!(RawAgency) {
+                    parsed!();
+                    if let Ok(timezone) = agency?.agency_timezone.parse() {
+                        self.timezone = timezone;
+                    }
+                }
+            }
+            "stops.txt" => {
+                for stop in records!(RawStop) {
+                    let stop = stop?;
+                    parsed!();
+                    // Entrances and other parts of stations do not have to be located.
+                    let (Some(latitude), Some(longitude)) = (stop.stop_lat, stop.stop_lon) else {
+                        continue;
+                    };
+                    let id: Arc<str> = stop.stop_id.into();
+                    self.stops.insert(
+                        id.clone(),
+                        Stop {
+                            id,
+                            code: stop.stop_code,
+                            name: stop.stop_name.unwrap_or_default(),
+                            position: walkers::lat_lon(latitude, longitude),
+                        },
+                    );
+                }
+            }
+            "routes.txt" => {
+                for route in records!(RawRoute) {
+                    let route = route?;
+                    parsed!();
+                    let short_name = route
+                        .route_short_name
+                        .unwrap_or_else(|| route.route_id.clone());
+                    self.routes_by_name
+                        .insert(short_name.clone(), route.route_id.clone());
+                    self.routes.insert(
+                        route.route_id.clone(),
+                        Route {
+                            id: route.route_id,
+                            short_name,
+                            long_name: route.route_long_name.unwrap_or_default(),
+                            route_type: route.route_type,
+                        },
+                    );
+                }
+            }
+            "calendar.txt" => {
+                for service in records!(RawCalendar) {
+                    let service = service?;
+                    parsed!();
+                    let (Some(start), Some(end)) =
+                        (date(&service.start_date), date(&service.end_date))
+                    else {
+                        log::warn!("Invalid period of service {}.", service.service_id);
+                        continue;
+                    };
+                    self.services.insert(
+                        service.service_id,
+                        Service {
+                            weekdays: [
+                                service.monday == 1,
+                                service.tuesday == 1,
+                                service.wednesday == 1,
+                                service.thursday == 1,
+                                service.friday == 1,
+                                service.saturday == 1,
+                                service.sunday == 1,
+                            ],
+                            start,
+                            end,
+                        },
+                    );
+                }
+            }
+            "calendar_dates.txt" => {
+                for exception in records!(RawCalendarDate) {
+                    let exception = exception?;
+                    parsed!();
+                    let Some(date) = date(&exception.date) else {
+                        log::warn!("Invalid date of service {}.", exception.service_id);
+                        continue;
+                    };
+                    self.exceptions
+                        .insert((exception.service_id, date), exception.exception_type == 1);
+                }
+            }
+            "trips.txt" => {
+                for trip in records!(RawTrip) {
+                    let trip = trip?;
+                    parsed!();
+                    let id: Arc<str> = trip.trip_id.into();
+                    self.trips_by_route
+                        .entry(trip.route_id.clone())
+                        .or_default()
+                        .push(id.clone());
+                    self.trips.insert(
+                        id.clone(),
+                        Trip {
+                            id,
+                            route_id: trip.route_id,
+                            service_id: trip.service_id,
+                            headsign: trip.trip_headsign,
+                            direction: trip.direction_id,
+                            shape_id: trip.shape_id,
+                            brigade: trip.brigade_id,
+                        },
+                    );
+                }
+            }
+            "shapes.txt" => {
+                let mut points: HashMap<String, Vec<(u32, Position)>> = HashMap::new();
+                for point in records!(RawShapePoint) {
+                    let point = point?;
+                    parsed!();
+                    points.entry(point.shape_id).or_default().push((
+                        point.shape_pt_sequence,
+                        walkers::lat_lon(point.shape_pt_lat, point.shape_pt_lon),
+                    ));
+                }
+                self.shapes = points
+                    .into_iter()
+                    .map(|(id, mut points)| {
+                        points.sort_by_key(|(sequence, _)| *sequence);
+                        (id, points.into_iter().map(|(_, point)| point).collect())
+                    })
+                    .collect();
+            }
+            "stop_times.txt" => {
+                let mut stop_times: HashMap<Arc<str>, Vec<(u32, StopTime)>> = HashMap::new();
+                for stop_time in records!(RawStopTime) {
+                    let stop_time = stop_time?;
+                    parsed!();
+                    let arrival = stop_time.arrival_time.as_deref().and_then(seconds);
+                    let departure = stop_time.departure_time.as_deref().and_then(seconds);
+                    // Calls between timepoints might have no time at all.
+                    let Some((arrival, departure)) =
+                        arrival.or(departure).zip(departure.or(arrival))
+                    else {
+                        continue;
+                    };
+                    stop_times
+                        .entry(intern(&self.trips, stop_time.trip_id))
+                        .or_default()
+                        .push((
+                            stop_time.stop_sequence,
+                            StopTime {
+                                stop_id: intern(&self.stops, stop_time.stop_id),
+                                arrival,
+                                departure,
+                            },
+                        ));
+                }
+
+                for (trip_id, mut calls) in stop_times {
+                    calls.sort_by_key(|(sequence, _)| *sequence);
+                    for (n, (_, stop_time)) in calls.iter().enumerate() {
+                        self.calls
+                            .entry(stop_time.stop_id.clone())
+                            .or_default()
+                            .push((trip_id.clone(), n));
+                    }
+                    self.stop_times.insert(
+                        trip_id,
+                        calls.into_iter().map(|(_, stop_time)| stop_time).collect(),
+                    );
+                }
+            }
+            _ => unreachable!("unknown file {file}"),
+        }
+
+        Ok(())
+    }
+}
+
+/// The same id as the one already in the map, if it is there, so it is kept in memory once.
+fn intern<V>(known: &HashMap<Arc<str>, V>, id: String) -> Arc<str> {
+    known
+        .get_key_value(id.as_str())
+        .map_or_else(|| id.into(), |(known, _)| known.clone())
+}
+
+/// Read a file of the feed, ignoring the directory it is in, since some feeds are zipped
+/// together with their directory.
+fn read(archive: &mut Archive, name: &str) -> Result<Option<Vec<u8>>, GtfsError> {
+    let Some(path) = archive
+        .file_names()
+        .find(|path| path.rsplit('/').next() == Some(name))
+        .map(String::from)
+    else {
+        return Ok(None);
+    };
+
+    let mut contents = Vec::new();
+    archive.by_name(&path)?.read_to_end(&mut contents)?;
+    Ok(Some(contents))
+}
+
+/// Sort names of lines the way people expect, numbers first, and in numerical order.
+pub(crate) fn sort_lines(lines: &mut [&str]) {
+    lines.sort_by_key(|line| (line.parse::<u32>().is_err(), line.len(), *line));
+}
+
+/// Date in the `YYYYMMDD` format of GTFS.
+fn date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y%m%d").ok()
+}
+
+/// Time in the `H:MM:SS` format of GTFS, which goes past 24:00:00 for trips after midnight.
+fn seconds(time: &str) -> Option<u32> {
+    let mut parts = time.split(':').map(|part| part.parse::<u32>().ok());
+    let (Some(hours), Some(minutes), Some(seconds), None) =
+        (parts.next()?, parts.next()?, parts.next()?, parts.next())
+    else {
+        return None;
+    };
+    Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+#[derive(Deserialize)]
+struct RawAgency {
+    agency_timezone: String,
+}
+
+#[derive(Deserialize)]
+struct RawStop {
+    stop_id: String,
+    #[serde(default)]
+    stop_code: Option<String>,
+    #[serde(default)]
+    stop_name: Option<String>,
+    #[serde(default)]
+    stop_lat: Option<f64>,
+    #[serde(default)]
+    stop_lon: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct RawRoute {
+    route_id: String,
+    #[serde(default)]
+    route_short_name: Option<String>,
+    #[serde(default)]
+    route_long_name: Option<String>,
+    route_type: u32,
+}
+
+#[derive(Deserialize)]
+struct RawCalendar {
+    service_id: String,
+    monday: u8,
+    tuesday: u8,
+    wednesday: u8,
+    thursday: u8,
+    friday: u8,
+    saturday: u8,
+    sunday: u8,
+    start_date: String,
+    end_date: String,
+}
+
+#[derive(Deserialize)]
+struct RawCalendarDate {
+    service_id: String,
+    date: String,
+    exception_type: u8,
+}
+
+#[derive(Deserialize)]
+struct RawTrip {
+    route_id: String,
+    service_id: String,
+    trip_id: String,
+    #[serde(default)]
+    trip_headsign: Option<String>,
+    #[serde(default)]
+    direction_id: Option<u8>,
+    #[serde(default)]
+    shape_id: Option<String>,
+    #[serde(default)]
+    brigade_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawShapePoint {
+    shape_id: String,
+    shape_pt_lat: f64,
+    shape_pt_lon: f64,
+    shape_pt_sequence: u32,
+}
+
+#[derive(Deserialize)]
+struct RawStopTime {
+    trip_id: String,
+    #[serde(default)]
+    arrival_time: Option<String>,
+    #[serde(default)]
+    departure_time: Option<String>,
+    stop_id: String,
+    stop_sequence: u32,
+}
+
+/// How far the loading went.
+#[derive(Debug, Clone)]
+pub enum LoadStatus {
+    Downloading,
+    /// Reading given file of the feed.
+    Loading(&'static str),
+    Loaded,
+    Failed(Arc<GtfsError>),
+}
+
+struct Shared {
+    schedule: Mutex<Arc<Schedule>>,
+    status: Mutex<LoadStatus>,
+}
+
+/// Timetable, loading in the background.
+pub struct Gtfs {
+    _runtime: crate::io::Runtime,
+    shared: Arc<Shared>,
+}
+
+impl Gtfs {
+    /// Start loading the timetable. Downloaded timetables are kept in `cache_dir`, if given.
+    pub fn load(source: Source, cache_dir: Option<PathBuf>, egui_ctx: egui::Context) -> Self {
+        let shared = Arc::new(Shared {
+            schedule: Default::default(),
+            status: Mutex::new(LoadStatus::Downloading),
+        });
+
+        let runtime = crate::io::Runtime::new({
+            let shared = shared.clone();
+            async move {
+                let result = load(&source, cache_dir.as_deref(), &shared, &egui_ctx).await;
+                *shared.status.lock().unwrap() = match result {
+                    Ok(()) => LoadStatus::Loaded,
+                    Err(err) => {
+                        log::error!("Could not load the timetable: {err}");
+                        LoadStatus::Failed(Arc::new(err))
+                    }
+                };
+                egui_ctx.request_repaint();
+            }
+        });
+
+        Self {
+            _runtime: runtime,
+            shared,
+        }
+    }
+
+    /// Latest snapshot of the timetable. Might be incomplete, or even empty, while loading.
+    pub fn schedule(&self) -> Arc<Schedule> {
+        self.shared.schedule.lock().unwrap().clone()
+    }
+
+    pub fn status(&self) -> LoadStatus {
+        self.shared.status.lock().unwrap().clone()
+    }
+}
+
+/// On the web, loading runs on the UI thread, so let it draw a frame now and then. Elsewhere
+/// it runs on its own thread, and does not need to.
+async fn pause() {
+    #[cfg(target_arch = "wasm32")]
+    wasmtimer::tokio::sleep(std::time::Duration::ZERO).await;
+}
+
+async fn load(
+    source: &Source,
+    cache_dir: Option<&Path>,
+    shared: &Shared,
+    egui_ctx: &egui::Context,
+) -> Result<(), GtfsError> {
+    let zip = match source {
+        Source::File(path) => std::fs::read(path)?,
+        Source::Url(url) => download(url, cache_dir).await?,
+    };
+
+    let mut archive = Archive::new(std::io::Cursor::new(&zip))?;
+    let mut schedule = Schedule::default();
+
+    for (n, file) in FILES.into_iter().enumerate() {
+        log::debug!("Loading {file}.");
+        *shared.status.lock().unwrap() = LoadStatus::Loading(file);
+        schedule.load(&mut archive, file, pause).await?;
+
+        // Stop times are by far the largest and go last, so the snapshots before are cheap
+        // to copy, and the last one is not copied at all.
+        *shared.schedule.lock().unwrap() = if n + 1 == FILES.len() {
+            Arc::new(std::mem::take(&mut schedule))
+        } else {
+            Arc::new(schedule.clone())
+        };
+        egui_ctx.request_repaint();
+        pause().await;
+    }
+
+    let schedule = shared.schedule.lock().unwrap().clone();
+    log::info!(
+        "Timetable loaded: {} stops, {} routes, {} trips.",
+        schedule.stops.len(),
+        schedule.routes.len(),
+        schedule.trips.len()
+    );
+    Ok(())
+}
+
+/// Download the timetable, unless the cached one is still up to date. Cached one is also used
+/// when the server cannot be reached.
+async fn download(url: &str, cache_dir: Option<&Path>) -> Result<Vec<u8>, GtfsError> {
+    let path = cache_dir.map(|dir| {
+        let mut hasher = DefaultHasher::new();
+        url.hash(&mut hasher);
+        dir.join(format!("{:x}.zip", hasher.finish()))
+    });
+    let cached = path.as_ref().and_then(|path| {
+        let zip = std::fs::read(path).ok()?;
+        let etag = std::fs::read_to_string(path.with_extension("etag")).ok();
+        Some((zip, etag))
+    });
+
+    let mut request = reqwest::Client::new().get(url);
+    if let Some((_, Some(etag))) = &cached {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag.as_str());
+    }
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(err) => {
+            return cached
+                .map(|(zip, _)| zip)
+                .ok_or(err.into())
+                .inspect(|_| log::warn!("Could not download the timetable, using the cached one."))
+        }
+    };
+
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        if let Some((zip, _)) = cached {
+            log::info!("Cached timetable is up to date.");
+            return Ok(zip);
+        }
+    }
+
+    if !response.status().is_success() {
+        let status = response.status();
+        return cached
+            .map(|(zip, _)| zip)
+            .ok_or(GtfsError::Status(status))
+            .inspect(|_| {
+                log::warn!("Server responded with {status}, using the cached timetable.")
+            });
+    }
+
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(str::to_string);
+    let zip = response.bytes().await?.to_vec();
+
+    if let Some(path) = path {
+        let write = || -> std::io::Result<()> {
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(&path, &zip)?;
+            match &etag {
+                Some(etag) => std::fs::write(path.with_extension("etag"), etag),
+                None => std::fs::remove_file(path.with_extension("etag")).or(Ok(())),
+            }
+        };
+        if let Err(err) = write() {
+            log::warn!("Could not cache the timetable: {err}");
+        }
+    }
+
+    Ok(zip)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use chrono_tz::Europe::Warsaw;
+
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn timetable_is_indexed() {
+        let schedule = testing::schedule();
+
+        assert_eq!(schedule.stops.len(), 3);
+        assert_eq!(schedule.stops["2"].name, "Galeria Dominikańska");
+        assert_eq!(schedule.stops["2"].code.as_deref(), Some("10002"));
+
+        let route = schedule.route_by_name("1").unwrap();
+        assert_eq!(route.category(), Category::Tram);
+        assert_eq!(schedule.trips["3_1"].brigade.as_deref(), Some("101"));
+
+        let stops: Vec<_> = schedule
+            .stop_times("3_1")
+            .iter()
+            .map(|stop_time| &*stop_time.stop_id)
+            .collect();
+        assert_eq!(stops, vec!["1", "2", "3"]);
+        assert_eq!(
+            schedule.stop_times("3_1")[1].departure,
+            8 * 3600 + 5 * 60 + 30
+        );
+
+        let mut calls: Vec<_> = schedule
+            .calls_at("3")
+            .map(|(trip, _, stop_time)| (&*trip.id, stop_time.arrival))
+            .collect();
+        calls.sort();
+        assert_eq!(
+            calls,
+            vec![("3_1", 8 * 3600 + 600), ("3_2", 24 * 3600 + 600)]
+        );
+
+        assert_eq!(schedule.lines_at("2"), vec!["1", "145"]);
+        assert_eq!(schedule.shapes_of(&route.id).len(), 2);
+
+        assert_eq!(
+            schedule.shapes["1"],
+            vec![
+                walkers::lat_lon(51.0990, 17.0360),
+                walkers::lat_lon(51.1080, 17.0400),
+                walkers::lat_lon(51.1120, 17.0600),
+            ]
+        );
+    }
+
+    #[test]
+    fn services_follow_the_calendar() {
+        let schedule = testing::schedule();
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        assert!(schedule.runs_on("6", date(2026, 10, 19)), "Monday");
+        assert!(!schedule.runs_on("6", date(2026, 10, 18)), "Sunday");
+        assert!(!schedule.runs_on("6", date(2026, 1, 1)), "removed");
+        assert!(schedule.runs_on("6", date(2026, 10, 17)), "added");
+        assert!(!schedule.runs_on("6", date(2031, 1, 6)), "after the period");
+    }
+
+    #[test]
+    fn service_day_goes_past_midnight() {
+        let schedule = testing::schedule();
+        let date = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+
+        assert_eq!(
+            schedule.time(date, 24 * 3600 + 600),
+            Warsaw.with_ymd_and_hms(2026, 10, 20, 0, 10, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn timetable_without_stop_times_is_rejected() {
+        let files: Vec<_> = testing::timetable::FILES
+            .iter()
+            .filter(|(name, _)| *name != "stop_times.txt")
+            .copied()
+            .collect();
+
+        assert!(matches!(
+            Schedule::parse(&testing::timetable::zip(&files)),
+            Err(GtfsError::MissingFile("stop_times.txt"))
+        ));
+    }
+
+    #[test]
+    fn timetable_loads_in_background() {
+        let path = std::env::temp_dir().join(format!("wrowalk-{}.zip", std::process::id()));
+        std::fs::write(&path, testing::timetable::zip(testing::timetable::FILES)).unwrap();
+
+        let gtfs = Gtfs::load(Source::File(path.clone()), None, egui::Context::default());
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !matches!(gtfs.status(), LoadStatus::Loaded) {
+            assert!(Instant::now() < deadline, "timetable did not load");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(gtfs.schedule().trips.len(), 3);
+    }
+
+    #[test]
+    fn large_files_are_parsed_in_chunks() {
+        let stops: String = std::iter::once("stop_id,stop_name,stop_lat,stop_lon\n".to_string())
+            .chain((0..CHUNK * 5 / 2).map(|n| format!("{n},Stop {n},51.1,17.0\n")))
+            .collect();
+        let zip = testing::timetable::zip(&[("stops.txt", &stops)]);
+        let mut archive = Archive::new(std::io::Cursor::new(&zip)).unwrap();
+        let mut schedule = Schedule::default();
+
+        let pauses = std::cell::Cell::new(0);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime
+            .block_on(schedule.load(&mut archive, "stops.txt", async || {
+                pauses.set(pauses.get() + 1)
+            }))
+            .unwrap();
+
+        assert_eq!(schedule.stops.len(), CHUNK * 5 / 2);
+        assert_eq!(pauses.get(), 2);
+    }
+}
@@ -0,0 +1,288 @@
+//! Vehicle positions published as GTFS-Realtime, which is what most operators use.
+//! https://gtfs.org/documentation/realtime/reference/
+
+use chrono::{DateTime, Utc};
+use prost::Message as _;
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    clock::Clock,
+    feed::{poll_continuously, FeedError, FeedSettings, FeedStatus, Report, Shared, VehicleFeed},
+    gtfs::Schedule,
+    http::{Downloader, Fetched},
+    vehicle::Vehicle,
+};
+
+/// Subset of `gtfs-realtime.proto` needed to read vehicle positions. Fields which are not
+/// listed here are skipped while decoding.
+mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FeedMessage {
+        #[prost(message, repeated, tag = "2")]
+        pub entity: Vec<FeedEntity>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FeedEntity {
+        #[prost(string, required, tag = "1")]
+        pub id: String,
+        #[prost(bool, optional, tag = "2")]
+        pub is_deleted: Option<bool>,
+        #[prost(message, optional, tag = "4")]
+        pub vehicle: Option<VehiclePosition>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct VehiclePosition {
+        #[prost(message, optional, tag = "1")]
+        pub trip: Option<TripDescriptor>,
+        #[prost(message, optional, tag = "2")]
+        pub position: Option<Position>,
+        /// POSIX time at which the position was measured.
+        #[prost(uint64, optional, tag = "5")]
+        pub timestamp: Option<u64>,
+        #[prost(message, optional, tag = "8")]
+        pub vehicle: Option<VehicleDescriptor>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TripDescriptor {
+        #[prost(string, optional, tag = "1")]
+        pub trip_id: Option<String>,
+        #[prost(string, optional, tag = "5")]
+        pub route_id: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Position {
+        #[prost(float, required, tag = "1")]
+        pub latitude: f32,
+        #[prost(float, required, tag = "2")]
+        pub longitude: f32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct VehicleDescriptor {
+        #[prost(string, optional, tag = "1")]
+        pub id: Option<String>,
+        #[prost(string, optional, tag = "2")]
+        pub label: Option<String>,
+        #[prost(string, optional, tag = "3")]
+        pub license_plate: Option<String>,
+    }
+}
+
+async fn fetch_vehicles(
+    downloader: &mut Downloader,
+    schedule: &Schedule,
+    full: bool,
+) -> Result<Fetched<Vec<Report>>, FeedError> {
+    log::info!("Fetching vehicles from {}.", downloader.url());
+
+    if full {
+        downloader.invalidate();
+    }
+
+    let Fetched::New(bytes) = downloader.get().await? else {
+        return Ok(Fetched::Unchanged);
+    };
+
+    parse_vehicles(&bytes, schedule)
+        .inspect_err(|_| downloader.invalidate())
+        .map(Fetched::New)
+}
+
+fn parse_vehicles(bytes: &[u8], schedule: &Schedule) -> Result<Vec<Report>, FeedError> {
+    let message = proto::FeedMessage::decode(bytes)?;
+
+    Ok(message
+        .entity
+        .iter()
+        .filter(|entity| !entity.is_deleted())
+        .filter_map(|entity| report(entity, schedule))
+        .collect())
+}
+
+/// Turn the entity into a [`Report`], if it carries enough information.
+fn report(entity: &proto::FeedEntity, schedule: &Schedule) -> Option<Report> {
+    let vehicle = entity.vehicle.as_ref()?;
+    let position = vehicle.position.as_ref()?;
+    let line = line(vehicle.trip.as_ref()?, schedule)?;
+
+    let id = vehicle
+        .vehicle
+        .as_ref()
+        .and_then(|descriptor| descriptor.id.clone())
+        .unwrap_or_else(|| entity.id.clone());
+
+    Some(Report {
+        id,
+        line,
+        brigade: None,
+        registration: vehicle
+            .vehicle
+            .as_ref()
+            .and_then(|descriptor| descriptor.license_plate.clone()),
+        position: walkers::lat_lon(position.latitude as f64, position.longitude as f64),
+        time: vehicle
+            .timestamp
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp as i64, 0))
+            .unwrap_or_else(Utc::now),
+    })
+}
+
+/// Name of the line, e.g. "33". `route_id` is an opaque id, so it is resolved to the
+/// `route_short_name` through the timetable, looking the route up by the trip if needed.
+/// Until the timetable is loaded, or if it does not know the route, the id is used as it is.
+/// For some operators, like MPK Wrocław, it is the name anyway. `vehicle.label` is not used,
+/// since operators put fleet numbers there as often as line names.
+fn line(trip: &proto::TripDescriptor, schedule: &Schedule) -> Option<String> {
+    let route_id = match &trip.route_id {
+        Some(route_id) => route_id.as_str(),
+        None => schedule
+            .trips
+            .get(trip.trip_id.as_deref()?)?
+            .route_id
+            .as_str(),
+    };
+
+    Some(
+        schedule
+            .routes
+            .get(route_id)
+            .map_or_else(|| route_id.to_string(), |route| route.short_name.clone()),
+    )
+}
+
+/// Tracks vehicles of an operator publishing GTFS-Realtime `VehiclePositions`.
+pub struct GtfsRealtime {
+    name: String,
+    url: String,
+    runtime: Option<crate::io::Runtime>,
+    shared: Arc<Shared>,
+}
+
+impl GtfsRealtime {
+    pub fn new(name: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            url: url.into(),
+            runtime: None,
+            shared: Default::default(),
+        }
+    }
+
+    /// Use a different clock, e.g. one which does not really sleep. Has to be called before
+    /// the feed is started.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.shared = Arc::new(self.shared.replace_clock(clock));
+        self
+    }
+}
+
+impl VehicleFeed for GtfsRealtime {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn start(&mut self, egui_ctx: egui::Context) {
+        if self.runtime.is_none() {
+            let mut downloader = Downloader::new(&self.url);
+            let shared = self.shared.clone();
+            self.runtime = Some(crate::io::Runtime::new(poll_continuously(
+                self.shared.clone(),
+                egui_ctx,
+                async move |full| fetch_vehicles(&mut downloader, &shared.schedule(), full).await,
+            )));
+        }
+    }
+
+    fn vehicles(&self) -> HashMap<String, Vehicle> {
+        self.shared.vehicles()
+    }
+
+    fn status(&self) -> FeedStatus {
+        self.shared.status()
+    }
+
+    fn settings(&self) -> FeedSettings {
+        self.shared.settings()
+    }
+
+    fn set_settings(&mut self, settings: FeedSettings) {
+        self.shared.set_settings(settings);
+    }
+
+    fn set_schedule(&mut self, schedule: Arc<Schedule>) {
+        self.shared.set_schedule(schedule);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gtfs::Route;
+
+    const FIXTURE: &[u8] = include_bytes!("../../wrowalk_mock/fixtures/vehicle_positions.pb");
+
+    fn schedule() -> Schedule {
+        let mut schedule = Schedule::default();
+        schedule.routes.insert(
+            "1".to_string(),
+            Route {
+                id: "1".to_string(),
+                short_name: "33".to_string(),
+                long_name: "Pilczyce - Sępolno".to_string(),
+                route_type: 0,
+            },
+        );
+        schedule
+    }
+
+    #[test]
+    fn deleted_entities_and_ones_without_position_are_skipped() {
+        let reports = parse_vehicles(FIXTURE, &Schedule::default()).unwrap();
+
+        assert_eq!(
+            reports
+                .iter()
+                .map(|report| report.id.as_str())
+                .collect::<Vec<_>>(),
+            ["2401", "8231"]
+        );
+        assert_eq!(reports[0].registration.as_deref(), Some("DW 12345"));
+    }
+
+    #[test]
+    fn route_id_is_resolved_to_line_name() {
+        let reports = parse_vehicles(FIXTURE, &schedule()).unwrap();
+
+        assert_eq!(reports[0].line, "33");
+        // Route not in the timetable.
+        assert_eq!(reports[1].line, "145");
+    }
+
+    #[test]
+    fn route_is_looked_up_by_trip_if_missing() {
+        let mut schedule = schedule();
+        schedule.trips.insert(
+            "3_1".into(),
+            crate::gtfs::Trip {
+                id: "3_1".into(),
+                route_id: "1".to_string(),
+                service_id: "6".to_string(),
+                headsign: None,
+                direction: None,
+                shape_id: None,
+                brigade: None,
+            },
+        );
+        let trip = proto::TripDescriptor {
+            trip_id: Some("3_1".to_string()),
+            route_id: None,
+        };
+
+        assert_eq!(line(&trip, &schedule).as_deref(), Some("33"));
+        assert_eq!(line(&trip, &Schedule::default()), None);
+    }
+}
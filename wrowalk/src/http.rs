@@ -0,0 +1,166 @@
+//! Polling HTTP resources without downloading or processing the same payload twice.
+
+use reqwest::{
+    header::{HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use std::hash::{DefaultHasher, Hash as _, Hasher as _};
+
+use crate::feed::FeedError;
+
+/// Result of a poll which might have found nothing new.
+#[derive(Debug)]
+pub(crate) enum Fetched<T> {
+    New(T),
+    Unchanged,
+}
+
+/// Downloads the same URL over and over. Uses `ETag` and `Last-Modified` validators when the
+/// server supports them, and falls back to comparing hashes of the bodies when it does not.
+pub(crate) struct Downloader {
+    client: reqwest::Client,
+    url: String,
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    hash: Option<u64>,
+}
+
+impl Downloader {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+            etag: None,
+            last_modified: None,
+            hash: None,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub async fn get(&mut self) -> Result<Fetched<bytes::Bytes>, FeedError> {
+        let mut request = self.client.get(&self.url);
+
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+
+        let response = request.send().await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::Unchanged);
+        }
+
+        if !response.status().is_success() {
+            return Err(FeedError::status(&response));
+        }
+
+        let etag = response.headers().get(ETAG).cloned();
+        let last_modified = response.headers().get(LAST_MODIFIED).cloned();
+        let bytes = response.bytes().await?;
+
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        // Some servers change validators even if the body stays the same. Keeping the old ones
+        // would make every later request download the body again.
+        self.etag = etag;
+        self.last_modified = last_modified;
+
+        if self.hash == Some(hash) {
+            return Ok(Fetched::Unchanged);
+        }
+
+        self.hash = Some(hash);
+
+        Ok(Fetched::New(bytes))
+    }
+
+    /// Forget what was downloaded, so the next [`Downloader::get`] returns the payload even if
+    /// it did not change. Useful when the payload turned out to be broken.
+    pub fn invalidate(&mut self) {
+        self.etag = None;
+        self.last_modified = None;
+        self.hash = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead as _, BufReader, Write as _},
+        net::TcpListener,
+        thread::JoinHandle,
+    };
+
+    use super::*;
+
+    /// Serve the same body with a new `ETag` every time. Thread returns the `If-None-Match`
+    /// headers of the requests.
+    fn serve_rotating_etags(requests: usize) -> (String, JoinHandle<Vec<Option<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        let thread = std::thread::spawn(move || {
+            (0..requests)
+                .map(|n| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut if_none_match = None;
+                    for line in BufReader::new(&stream).lines() {
+                        let line = line.unwrap();
+                        if line.is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(": ") {
+                            if name.eq_ignore_ascii_case("if-none-match") {
+                                if_none_match = Some(value.to_string());
+                            }
+                        }
+                    }
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nETag: \"{n}\"\r\nContent-Length: 4\r\nConnection: close\r\n\r\nbody"
+                    )
+                    .unwrap();
+                    if_none_match
+                })
+                .collect()
+        });
+
+        (url, thread)
+    }
+
+    #[test]
+    fn validators_are_kept_fresh_when_body_is_the_same() {
+        let (url, server) = serve_rotating_etags(3);
+        let mut downloader = Downloader::new(url);
+
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                assert!(matches!(downloader.get().await.unwrap(), Fetched::New(_)));
+                assert!(matches!(
+                    downloader.get().await.unwrap(),
+                    Fetched::Unchanged
+                ));
+                assert!(matches!(
+                    downloader.get().await.unwrap(),
+                    Fetched::Unchanged
+                ));
+            });
+
+        assert_eq!(
+            server.join().unwrap(),
+            [None, Some("\"0\"".to_string()), Some("\"1\"".to_string())]
+        );
+    }
+}
@@ -1,71 +1,446 @@
+mod animation;
+mod category;
+pub mod clock;
+mod delay;
+mod departures;
+mod direction;
+pub mod feed;
+mod filter;
+mod geo;
+pub mod gtfs;
+pub mod gtfs_realtime;
+mod http;
 mod io;
-mod mpkwroclaw;
+mod markers;
+pub mod mpkwroclaw;
 mod places;
+mod polling;
+mod recording;
+mod route;
+mod search;
+mod settings;
+mod stops;
 mod style;
+#[cfg(test)]
+mod testing;
 mod tiles;
+pub mod vehicle;
 mod windows;
 
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
-use egui::{CentralPanel, Color32, Context, FontId, Frame};
+use category::Category;
+use chrono::{TimeDelta, Utc};
+use egui::{CentralPanel, Color32, Context, Frame};
+use feed::VehicleFeed;
 use itertools::Itertools as _;
+use markers::{Marker, Markers};
 use tiles::{providers, Provider, TilesKind};
-use walkers::{
-    extras::{LabeledSymbol, Places},
-    Map, MapMemory, Plugin,
-};
+use vehicle::{Vehicle, VehicleKey};
+use walkers::{Map, MapMemory, Plugin};
 
 pub struct MyApp {
     providers: BTreeMap<Provider, Vec<TilesKind>>,
     selected_provider: Provider,
     map_memory: MapMemory,
-    mpkwroclaw: mpkwroclaw::MpkWroclaw,
+    feeds: Vec<Box<dyn VehicleFeed>>,
+    /// Vehicles which did not report their position for this long are dimmed.
+    stale_after: TimeDelta,
+    /// How often healthy feeds are polled, see [`MyApp::set_poll_interval`].
+    poll_interval: Duration,
+    animator: animation::Animator,
+    gtfs: gtfs::Gtfs,
+    /// Snapshot of the timetable the feeds were given.
+    schedule: Arc<gtfs::Schedule>,
+    /// Draw vehicles snapped onto their routes, instead of where the feeds put them.
+    snap_to_routes: bool,
+    /// Stops are shown only when the map is zoomed in at least this much.
+    stops_min_zoom: f64,
+    selected_stop: Option<String>,
+    /// Line whose route is shown.
+    selected_line: Option<String>,
+    /// Vehicle whose details are shown, and which the map follows unless it is dragged away.
+    selected_vehicle: Option<VehicleKey>,
+    /// Where the selected vehicle was in the previous frame.
+    followed: Option<walkers::Position>,
+    line_filter: filter::LineFilter,
+    /// Ids of stops starred by the user, listed in the search window while nothing is typed.
+    favourites: BTreeSet<String>,
+    /// What is typed into the search box.
+    query: String,
 }
 
 impl MyApp {
     pub fn new(egui_ctx: Context) -> Self {
+        Self::with_cache_dir(egui_ctx, default_cache_dir())
+    }
+
+    /// Create the app keeping downloaded files in given directory. Needed on Android, where
+    /// there is no default one.
+    pub fn with_cache_dir(egui_ctx: Context, cache_dir: Option<PathBuf>) -> Self {
+        Self::with_feeds(egui_ctx, default_feeds(), cache_dir)
+    }
+
+    /// Create the app tracking vehicles from given feeds instead of the default ones.
+    pub fn with_feeds(
+        egui_ctx: Context,
+        mut feeds: Vec<Box<dyn VehicleFeed>>,
+        cache_dir: Option<PathBuf>,
+    ) -> Self {
         egui_ctx.set_style(style::amoled_friendly());
         egui_material_icons::initialize(&egui_ctx);
 
+        for feed in &mut feeds {
+            feed.start(egui_ctx.to_owned());
+        }
+
         Self {
             providers: providers(egui_ctx.to_owned()),
             selected_provider: Provider::OpenStreetMap,
             map_memory: MapMemory::default(),
-            mpkwroclaw: mpkwroclaw::MpkWroclaw::new(egui_ctx),
+            feeds,
+            stale_after: TimeDelta::minutes(2),
+            poll_interval: feed::FeedSettings::default().polling.interval,
+            animator: animation::Animator::default(),
+            gtfs: gtfs::Gtfs::load(
+                gtfs_source(),
+                cache_dir.map(|dir| dir.join("gtfs")),
+                egui_ctx.to_owned(),
+            ),
+            schedule: Default::default(),
+            snap_to_routes: true,
+            stops_min_zoom: 15.0,
+            selected_stop: None,
+            selected_line: None,
+            selected_vehicle: None,
+            followed: None,
+            line_filter: Default::default(),
+            favourites: BTreeSet::new(),
+            query: String::new(),
+        }
+    }
+
+    /// Restore the settings and the map saved in the storage, if there are any.
+    pub fn restore(mut self, storage: Option<&dyn eframe::Storage>) -> Self {
+        if let Some(settings) = storage.and_then(settings::load) {
+            self.apply(settings);
+        }
+        self
+    }
+
+    fn settings(&self) -> settings::Settings {
+        settings::Settings {
+            version: settings::VERSION,
+            provider: self.selected_provider,
+            zoom: Some(self.map_memory.zoom()),
+            center: self
+                .map_memory
+                .detached()
+                .map(|center| (center.y(), center.x())),
+            line_filter: self.line_filter.clone(),
+            selected_line: self.selected_line.clone(),
+            favourites: self.favourites.clone(),
+            snap_to_routes: self.snap_to_routes,
+            animation: self.animator.mode,
+            stops_min_zoom: self.stops_min_zoom,
+            stale_after_minutes: self.stale_after.num_minutes(),
+            poll_interval_seconds: self.poll_interval.as_secs(),
         }
     }
 
-    fn positions(&self) -> Vec<LabeledSymbol> {
-        self.mpkwroclaw
-            .vehicles()
+    fn apply(&mut self, settings: settings::Settings) {
+        // Provider might be gone, e.g. if the build has no Mapbox token anymore.
+        if self.providers.contains_key(&settings.provider) {
+            self.selected_provider = settings.provider;
+        }
+        if let Some(zoom) = settings.zoom {
+            let _ = self.map_memory.set_zoom(zoom);
+        }
+        if let Some((lat, lon)) = settings.center {
+            self.map_memory.center_at(walkers::lat_lon(lat, lon));
+        }
+        self.line_filter = settings.line_filter;
+        self.selected_line = settings.selected_line;
+        self.favourites = settings.favourites;
+        self.snap_to_routes = settings.snap_to_routes;
+        self.animator.mode = settings.animation;
+        self.stops_min_zoom = settings.stops_min_zoom;
+        self.stale_after = TimeDelta::minutes(settings.stale_after_minutes.max(1));
+        self.set_poll_interval(Duration::from_secs(settings.poll_interval_seconds.max(1)));
+    }
+
+    /// Poll every feed that often while it is healthy. Backoff after failures starts from it.
+    fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = interval;
+        for feed in &mut self.feeds {
+            let mut settings = feed.settings();
+            settings.polling.interval = interval;
+            feed.set_settings(settings);
+        }
+    }
+
+    /// Vehicles from all feeds.
+    fn vehicles(&self) -> HashMap<VehicleKey, Vehicle> {
+        self.feeds
+            .iter()
+            .enumerate()
+            .flat_map(|(n, feed)| {
+                feed.vehicles()
+                    .into_iter()
+                    .map(move |(id, vehicle)| (VehicleKey { feed: n, id }, vehicle))
+            })
+            .map(|(key, vehicle)| {
+                if self.snap_to_routes {
+                    (key, vehicle)
+                } else {
+                    (key, vehicle.unsnapped())
+                }
+            })
+            .collect()
+    }
+
+    /// Latest snapshot of the timetable, handed over to the feeds whenever it changes.
+    fn schedule(&mut self) -> Arc<gtfs::Schedule> {
+        let schedule = self.gtfs.schedule();
+        if !Arc::ptr_eq(&schedule, &self.schedule) {
+            for feed in &mut self.feeds {
+                feed.set_schedule(schedule.clone());
+            }
+            self.schedule = schedule.clone();
+        }
+        schedule
+    }
+
+    /// Category from the timetable, or guessed from the name of the line if it is not there.
+    fn category(&self, schedule: &gtfs::Schedule, line: &str) -> Category {
+        schedule
+            .route_by_name(line)
+            .map_or_else(|| Category::guess(line), gtfs::Route::category)
+    }
+
+    /// Select what was found and show it on the map.
+    fn pick(&mut self, hit: search::Hit) {
+        match hit {
+            search::Hit::Line(line) => {
+                if let Some(route) = self.schedule.route_by_name(&line) {
+                    let positions: Vec<_> = self
+                        .schedule
+                        .shapes_of(&route.id)
+                        .into_iter()
+                        .flat_map(|(_, shape)| shape.iter().copied())
+                        .collect();
+                    if let Some(center) = geo::center(&positions) {
+                        self.map_memory.center_at(center);
+                        let _ = self.map_memory.set_zoom(13.0);
+                    }
+                }
+                self.line_filter.set(&line, true);
+                self.selected_line = Some(line);
+            }
+            search::Hit::Vehicle { key, line, .. } => {
+                self.line_filter.set(&line, true);
+                // Map follows the selected vehicle.
+                self.selected_vehicle = Some(key);
+                self.map_memory.follow_my_position();
+                let _ = self.map_memory.set_zoom(16.0);
+            }
+            search::Hit::Stop { id, .. } => {
+                if let Some(stop) = self.schedule.stops.get(id.as_str()) {
+                    self.map_memory.center_at(stop.position);
+                    let _ = self.map_memory.set_zoom(self.stops_min_zoom.max(16.0));
+                }
+                self.selected_stop = Some(id);
+            }
+        }
+    }
+
+    /// Lines of the vehicles, plus hidden ones, so that they can be shown again.
+    fn lines_seen(
+        &self,
+        vehicles: &HashMap<VehicleKey, Vehicle>,
+    ) -> BTreeMap<Category, Vec<String>> {
+        let mut lines: BTreeMap<Category, Vec<&str>> = BTreeMap::new();
+        for line in vehicles
             .values()
-            .map(|vehicle| {
-                let position = vehicle.position();
-                walkers::extras::LabeledSymbol {
-                    position,
-                    label: "".to_string(),
-                    symbol: Some(walkers::extras::Symbol::Circle(vehicle.line.clone())),
-                    style: walkers::extras::LabeledSymbolStyle {
-                        label_corner_radius: 1.,
-                        symbol_size: 22.,
-                        symbol_background: Color32::BLACK.gamma_multiply(0.8),
-                        symbol_color: Color32::WHITE,
-                        symbol_font: FontId::proportional(10.),
-                        symbol_stroke: egui::Stroke::new(1., Color32::WHITE),
-                        ..Default::default()
-                    },
+            .map(|vehicle| vehicle.line.as_str())
+            .chain(self.line_filter.hidden())
+            .unique()
+        {
+            lines
+                .entry(self.category(&self.schedule, line))
+                .or_default()
+                .push(line);
+        }
+
+        lines
+            .into_iter()
+            .map(|(category, mut lines)| {
+                gtfs::sort_lines(&mut lines);
+                (category, lines.into_iter().map(String::from).collect())
+            })
+            .collect()
+    }
+
+    /// Destination and the next stop of the vehicle, and when it was seen if it is stale.
+    fn label(&self, vehicle: &Vehicle, stale: Option<TimeDelta>) -> String {
+        let mut lines = Vec::new();
+        if let Some(direction) = vehicle.direction() {
+            lines.push(format!("→ {}", direction.headsign));
+            if let Some(stop) = direction
+                .next_stop
+                .as_ref()
+                .and_then(|id| self.schedule.stops.get(id))
+            {
+                lines.push(format!("next: {}", stop.name));
+            }
+        }
+        if let Some(age) = stale {
+            lines.push(vehicle::last_seen_ago(age));
+        }
+        lines.join("\n")
+    }
+
+    /// Markers of the vehicles, drawn at their animated positions.
+    fn markers(
+        &self,
+        vehicles: &HashMap<VehicleKey, Vehicle>,
+        animated: &HashMap<VehicleKey, walkers::Position>,
+    ) -> Vec<Marker> {
+        let now = Utc::now();
+
+        vehicles
+            .iter()
+            .map(|(key, vehicle)| {
+                let age = vehicle.age(now);
+                let stale = age > self.stale_after;
+
+                Marker {
+                    key: key.clone(),
+                    position: animated[key],
+                    line: vehicle.line.clone(),
+                    category: self.category(&self.schedule, &vehicle.line),
+                    label: self.label(vehicle, stale.then_some(age)),
+                    delay: vehicle.trip().map(|trip| trip.delay),
+                    opacity: if stale { 0.4 } else { 1.0 },
                 }
             })
             .collect()
     }
 }
 
+/// MPK Wrocław, plus a GTFS-Realtime feed if `GTFS_RT_URL` is set.
+fn default_feeds() -> Vec<Box<dyn VehicleFeed>> {
+    let mut feeds: Vec<Box<dyn VehicleFeed>> = vec![Box::new(mpk_wroclaw())];
+
+    if let Ok(url) = std::env::var("GTFS_RT_URL") {
+        feeds.push(Box::new(gtfs_realtime::GtfsRealtime::new("GTFS-RT", url)));
+    }
+
+    feeds
+}
+
+/// MPK Wrocław feed, replaying a recording from `WROWALK_REPLAY` directory (at
+/// `WROWALK_REPLAY_SPEED`), or recording into `WROWALK_RECORD` directory, if these are set.
+/// `WROWALK_MPK_URL`, which can also be set at compile time for the web, replaces the portal.
+fn mpk_wroclaw() -> mpkwroclaw::MpkWroclaw {
+    if let Ok(dir) = std::env::var("WROWALK_REPLAY") {
+        let speed = std::env::var("WROWALK_REPLAY_SPEED")
+            .map_or(1.0, |speed| recording::parse_speed(&speed));
+
+        match recording::Replay::open(&dir, speed) {
+            Ok(replay) => return mpkwroclaw::MpkWroclaw::replay(replay),
+            Err(err) => log::error!("Could not open recording in '{dir}': {err}"),
+        }
+    }
+
+    let feed = match std::env::var("WROWALK_MPK_URL")
+        .ok()
+        .or(option_env!("WROWALK_MPK_URL").map(String::from))
+    {
+        Some(url) => mpkwroclaw::MpkWroclaw::with_url(url),
+        None => mpkwroclaw::MpkWroclaw::new(),
+    };
+
+    if let Ok(dir) = std::env::var("WROWALK_RECORD") {
+        match recording::Recorder::new(&dir, "csv") {
+            Ok(recorder) => return feed.with_recorder(recorder),
+            Err(err) => log::error!("Could not record into '{dir}': {err}"),
+        }
+    }
+
+    feed
+}
+
+/// Where downloaded files are kept, next to the tiles. Android has no default place, and there
+/// is no file system on the web.
+fn default_cache_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "android")
+        || cfg!(target_arch = "wasm32")
+        || std::env::var("NO_HTTP_CACHE").is_ok()
+    {
+        None
+    } else {
+        Some(".cache".into())
+    }
+}
+
+/// Timetable from `WROWALK_GTFS`, a path or URL which can also be set at compile time for the
+/// web, or the one of MPK Wrocław.
+fn gtfs_source() -> gtfs::Source {
+    std::env::var("WROWALK_GTFS")
+        .ok()
+        .or(option_env!("WROWALK_GTFS").map(String::from))
+        .map_or_else(gtfs::Source::default, |source| gtfs::Source::parse(&source))
+}
+
 impl eframe::App for MyApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        settings::save(storage, &self.settings());
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         CentralPanel::default().frame(Frame::NONE).show(ctx, |ui| {
-            let my_position = places::wroclaw_glowny();
+            let schedule = self.schedule();
+            let vehicles = self.vehicles();
+            let (animated, moving) =
+                self.animator
+                    .positions(&vehicles, &schedule, ctx.input(|i| i.time));
+            if moving {
+                ctx.request_repaint();
+            }
+            let lines_seen = self.lines_seen(&vehicles);
+            let shown: HashMap<VehicleKey, Vehicle> = vehicles
+                .iter()
+                .filter(|(_, vehicle)| self.line_filter.shows(&vehicle.line))
+                .map(|(id, vehicle)| (id.clone(), vehicle.clone()))
+                .collect();
+            let markers = self.markers(&shown, &animated);
 
-            let positions = self.positions();
+            // Selected vehicle takes the place of "my position", so the map follows it.
+            let my_position = match self
+                .selected_vehicle
+                .as_ref()
+                .and_then(|id| animated.get(id))
+            {
+                Some(position) => {
+                    self.followed = Some(*position);
+                    *position
+                }
+                None => {
+                    // Deselected or gone, so stay where it was instead of jumping away.
+                    if let Some(position) = self.followed.take() {
+                        if self.map_memory.detached().is_none() {
+                            self.map_memory.center_at(position);
+                        }
+                    }
+                    places::wroclaw_glowny()
+                }
+            };
 
             let tiles = self.providers.get_mut(&self.selected_provider).unwrap();
             let attributions: Vec<_> = tiles
@@ -73,16 +448,50 @@ impl eframe::App for MyApp {
                 .map(|tile| tile.as_ref().attribution())
                 .collect();
 
+            // Taps change these, so remember what they were.
+            let selected_stop = self.selected_stop.clone();
+            let selected_vehicle = self.selected_vehicle.clone();
+
             let mut map = Map::new(None, &mut self.map_memory, my_position).zoom_with_ctrl(false);
 
+            map = map.with_plugin(stops::Stops {
+                schedule: &schedule,
+                min_zoom: self.stops_min_zoom,
+                selected: &mut self.selected_stop,
+            });
+
+            // Route goes under the vehicles, so it is clear which of them are off it.
+            if let Some(route) = self
+                .selected_line
+                .as_ref()
+                .and_then(|line| route::Route::new(&schedule, line))
+            {
+                map = map.with_plugin(route);
+            }
+
             // Add a track of the last positions of vehicles.
-            for vehicle in self.mpkwroclaw.vehicles().values() {
+            for (id, vehicle) in &shown {
                 let mut positions = vehicle.positions();
                 positions.reverse();
+                // Start where the marker is, not where the vehicle was seen.
+                positions[0] = animated[id];
                 map = map.with_plugin(Track { positions });
             }
 
-            map = map.with_plugin(Places::new(positions));
+            // Add an arrow pointing where vehicles are heading to.
+            for (id, vehicle) in &shown {
+                if let Some(bearing) = vehicle.bearing() {
+                    map = map.with_plugin(Heading {
+                        position: animated[id],
+                        bearing,
+                    });
+                }
+            }
+
+            map = map.with_plugin(Markers {
+                markers,
+                selected: &mut self.selected_vehicle,
+            });
 
             // Add layers.
             for (n, tiles) in tiles.iter_mut().enumerate() {
@@ -92,12 +501,39 @@ impl eframe::App for MyApp {
 
             ui.add(map);
 
+            // Vehicles are drawn over stops, so they take the tap if it hits both.
+            if self.selected_vehicle.is_some() && self.selected_vehicle != selected_vehicle {
+                self.selected_stop = selected_stop;
+                self.map_memory.follow_my_position();
+            }
+
             // Show utility windows.
             {
                 use windows::*;
 
-                zoom(ui, &mut self.map_memory);
+                zoom(ui, &mut self.map_memory, self.followed.is_some());
                 acknowledge(self, ui, attributions);
+                legend(ui);
+                lines(ui, &mut self.line_filter, &lines_seen);
+
+                let hits = if self.query.trim().is_empty() {
+                    search::favourites(&schedule, &self.favourites)
+                } else {
+                    search::search(&self.query, &schedule, &vehicles, 10)
+                };
+                if let Some(hit) = search(ui, &mut self.query, &hits) {
+                    self.query.clear();
+                    self.pick(hit);
+                }
+                stop(
+                    ui,
+                    &schedule,
+                    &vehicles,
+                    &mut self.selected_stop,
+                    &mut self.selected_line,
+                    &mut self.favourites,
+                );
+                vehicle(ui, &schedule, &vehicles, &mut self.selected_vehicle);
             }
         });
     }
@@ -128,3 +564,40 @@ impl Plugin for Track {
         }
     }
 }
+
+/// Draws an arrow on the edge of the vehicle symbol, pointing where it is heading to.
+struct Heading {
+    position: walkers::Position,
+    /// Degrees clockwise from the north.
+    bearing: f64,
+}
+
+impl Plugin for Heading {
+    fn run(
+        self: Box<Self>,
+        ui: &mut egui::Ui,
+        _response: &egui::Response,
+        projector: &walkers::Projector,
+        _map_memory: &walkers::MapMemory,
+    ) {
+        // Just outside of the symbol.
+        const INNER: f32 = markers::SIZE / 2.0;
+        const OUTER: f32 = INNER + 7.0;
+        const HALF_WIDTH: f32 = 5.0;
+
+        let center = projector.project(self.position).to_pos2();
+        let angle = self.bearing.to_radians() as f32;
+        let forward = egui::vec2(angle.sin(), -angle.cos());
+        let side = forward.rot90();
+
+        ui.painter().add(egui::Shape::convex_polygon(
+            vec![
+                center + forward * OUTER,
+                center + forward * INNER + side * HALF_WIDTH,
+                center + forward * INNER - side * HALF_WIDTH,
+            ],
+            Color32::WHITE,
+            egui::Stroke::new(1.0, Color32::BLACK),
+        ));
+    }
+}
@@ -0,0 +1,184 @@
+//! Vehicle markers, shaped and coloured after the category of the vehicle.
+
+use chrono::TimeDelta;
+use egui::{vec2, Align2, Color32, FontId, Painter, Pos2, Rect, Shape, Stroke, StrokeKind};
+use walkers::{Plugin, Position};
+
+use crate::{
+    category::Category,
+    delay::{self, Punctuality},
+    vehicle::VehicleKey,
+};
+
+/// Width of the marker symbol, in points.
+pub(crate) const SIZE: f32 = 22.0;
+
+/// Markers are tappable a bit beyond their symbol, so they are easy to hit with a finger.
+const TAP_RADIUS: f32 = SIZE / 2.0 + 6.0;
+
+pub(crate) struct Marker {
+    pub key: VehicleKey,
+    pub position: Position,
+    pub line: String,
+    pub category: Category,
+    /// Shown below the symbol, unless empty.
+    pub label: String,
+    /// Shown in the corner of the symbol, if known.
+    pub delay: Option<TimeDelta>,
+    pub opacity: f32,
+}
+
+/// Draws vehicle markers and selects the tapped one.
+pub(crate) struct Markers<'a> {
+    pub markers: Vec<Marker>,
+    pub selected: &'a mut Option<VehicleKey>,
+}
+
+impl Plugin for Markers<'_> {
+    fn run(
+        self: Box<Self>,
+        ui: &mut egui::Ui,
+        response: &egui::Response,
+        projector: &walkers::Projector,
+        _map_memory: &walkers::MapMemory,
+    ) {
+        let painter = ui.painter();
+        let tapped = response
+            .clicked()
+            .then(|| response.interact_pointer_pos())
+            .flatten();
+        let mut closest: Option<(f32, VehicleKey)> = None;
+
+        for marker in self.markers {
+            let center = projector.project(marker.position).to_pos2();
+
+            if self.selected.as_ref() == Some(&marker.key) {
+                painter.circle_stroke(center, SIZE * 0.9, Stroke::new(3., Color32::YELLOW));
+            }
+
+            symbol(
+                painter,
+                center,
+                marker.category,
+                &marker.line,
+                marker.opacity,
+            );
+
+            if let Some(delay) = marker.delay {
+                badge(painter, center + vec2(SIZE / 2., -SIZE / 2.), delay);
+            }
+
+            if !marker.label.is_empty() {
+                let galley = painter.layout_no_wrap(
+                    marker.label,
+                    FontId::proportional(12.),
+                    Color32::WHITE.gamma_multiply(marker.opacity),
+                );
+                let rect = Align2::CENTER_TOP
+                    .anchor_size(center + vec2(0., SIZE / 2. + 4.), galley.size())
+                    .expand(2.);
+                painter.rect_filled(rect, 1., Color32::BLACK.gamma_multiply(0.8));
+                painter.galley(rect.shrink(2.).min, galley, Color32::WHITE);
+            }
+
+            if let Some(tapped) = tapped {
+                let distance = tapped.distance(center);
+                if distance < TAP_RADIUS
+                    && closest
+                        .as_ref()
+                        .is_none_or(|(closest, _)| distance < *closest)
+                {
+                    closest = Some((distance, marker.key));
+                }
+            }
+        }
+
+        if let Some((_, key)) = closest {
+            *self.selected = Some(key);
+        }
+    }
+}
+
+/// Paint the symbol of a category, with a text (usually the line) inside.
+pub(crate) fn symbol(
+    painter: &Painter,
+    center: Pos2,
+    category: Category,
+    text: &str,
+    opacity: f32,
+) {
+    let fill = category.color().gamma_multiply(0.9 * opacity);
+    let stroke = Stroke::new(1., Color32::WHITE.gamma_multiply(opacity));
+    let radius = SIZE / 2.;
+
+    match category {
+        Category::Tram => {
+            painter.rect(
+                Rect::from_center_size(center, vec2(SIZE, SIZE)),
+                3.,
+                fill,
+                stroke,
+                StrokeKind::Middle,
+            );
+        }
+        Category::Bus => {
+            painter.circle(center, radius, fill, stroke);
+        }
+        Category::NightBus => {
+            painter.add(Shape::convex_polygon(
+                polygon(center, radius, 6),
+                fill,
+                stroke,
+            ));
+        }
+        Category::Special => {
+            // Somewhat bigger, since corners of the diamond cut into the text.
+            painter.add(Shape::convex_polygon(
+                polygon(center, radius * 1.3, 4),
+                fill,
+                stroke,
+            ));
+        }
+    }
+
+    painter.text(
+        center,
+        Align2::CENTER_CENTER,
+        text,
+        FontId::proportional(10.),
+        Color32::WHITE.gamma_multiply(opacity),
+    );
+}
+
+/// Delay in whole minutes, coloured after [`Punctuality`].
+fn badge(painter: &Painter, center: Pos2, delay: TimeDelta) {
+    let minutes = delay::minutes(delay);
+    let text = if minutes == 0 {
+        "0".to_string()
+    } else {
+        format!("{minutes:+}")
+    };
+
+    let galley = painter.layout_no_wrap(text, FontId::proportional(9.), Color32::WHITE);
+    let rect = Rect::from_center_size(center, galley.size()).expand2(vec2(3., 1.));
+    painter.rect_filled(rect, 4., punctuality_color(Punctuality::of(delay)));
+    painter.galley(rect.center() - galley.size() / 2., galley, Color32::WHITE);
+}
+
+pub(crate) fn punctuality_color(punctuality: Punctuality) -> Color32 {
+    match punctuality {
+        Punctuality::Early => Color32::from_rgb(40, 120, 220),
+        Punctuality::OnTime => Color32::from_rgb(40, 150, 60),
+        Punctuality::Late => Color32::from_rgb(210, 50, 40),
+    }
+}
+
+/// Vertices of a regular polygon, the first one pointing up.
+fn polygon(center: Pos2, radius: f32, sides: usize) -> Vec<Pos2> {
+    (0..sides)
+        .map(|n| {
+            let angle = n as f32 * std::f32::consts::TAU / sides as f32;
+            center + vec2(angle.sin(), -angle.cos()) * radius
+        })
+        .collect()
+}
@@ -1,50 +1,95 @@
+use chrono::{DateTime, NaiveDateTime, TimeZone as _, Utc};
+use chrono_tz::Europe::Warsaw;
+use itertools::Itertools as _;
 use serde::Deserialize;
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-    time::Duration,
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use crate::{
+    clock::Clock,
+    feed::{poll_continuously, FeedError, FeedSettings, FeedStatus, Report, Shared, VehicleFeed},
+    gtfs::Schedule,
+    http::{Downloader, Fetched},
+    recording::{Recorder, Replay},
+    vehicle::Vehicle,
 };
 
-#[cfg(not(target_arch = "wasm32"))]
-use tokio::time::sleep;
+/// Columns which must be present in the dump for [`RawVehicleRecord`] to make sense.
+const REQUIRED_COLUMNS: &[&str] = &[
+    "Nr_Boczny",
+    "Nazwa_Linii",
+    "Ostatnia_Pozycja_Szerokosc",
+    "Ostatnia_Pozycja_Dlugosc",
+];
+
+/// Dump of the vehicle positions, refreshed by the city every few seconds.
+const URL: &str =
+    "https://www.wroclaw.pl/open-data/datastore/dump/a9b3841d-e977-474e-9e86-8789e470a85a";
 
-#[cfg(target_arch = "wasm32")]
-use wasmtimer::tokio::sleep;
+/// Where the dumps come from.
+enum Source {
+    Http(Downloader),
+    Replay(Replay),
+}
 
-use walkers::Position;
+async fn fetch_vehicles(
+    source: &mut Source,
+    recorder: Option<&Recorder>,
+    full: bool,
+) -> Result<Fetched<Vec<Report>>, FeedError> {
+    match source {
+        Source::Http(downloader) => {
+            log::info!("Fetching vehicles from Wroclaw Open Data.");
 
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::OnceLock;
+            if full {
+                downloader.invalidate();
+            }
 
-static APP_IN_BACKGROUND: OnceLock<AtomicBool> = OnceLock::new();
+            let Fetched::New(bytes) = downloader.get().await? else {
+                return Ok(Fetched::Unchanged);
+            };
 
-#[no_mangle]
-pub extern "C" fn Java_com_github_podusowski_wrowalk_MainActivity_setAppInBackground(
-    _env: jni::JNIEnv,
-    _class: jni::objects::JClass,
-    is_background: bool,
-) {
-    let atomic = APP_IN_BACKGROUND.get_or_init(|| AtomicBool::new(false));
-    atomic.store(is_background, Ordering::SeqCst);
-}
+            if let Some(recorder) = recorder {
+                if let Err(err) = recorder.record(Utc::now(), &bytes) {
+                    log::warn!("Could not record the dump: {err}");
+                }
+            }
 
-pub fn is_app_in_background() -> bool {
-    APP_IN_BACKGROUND
-        .get()
-        .map(|a| a.load(Ordering::SeqCst))
-        .unwrap_or(false)
+            let records = parse_vehicles(&bytes).inspect_err(|_| downloader.invalidate())?;
+            Ok(Fetched::New(
+                records.iter().map(RawVehicleRecord::report).collect(),
+            ))
+        }
+        Source::Replay(replay) => {
+            let bytes = replay.next().await?;
+            Ok(Fetched::New(
+                parse_vehicles(&bytes)?
+                    .iter()
+                    .map(|record| {
+                        let mut report = record.report();
+                        report.time = replay.rebase(report.time);
+                        report
+                    })
+                    .collect(),
+            ))
+        }
+    }
 }
 
-async fn fetch_vehicles() -> Vec<RawVehicleRecord> {
-    log::info!("Fetching vehicles from Wroclaw Open Data.");
+fn parse_vehicles(bytes: &[u8]) -> Result<Vec<RawVehicleRecord>, FeedError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .from_reader(bytes);
 
-    let url =
-        "https://www.wroclaw.pl/open-data/datastore/dump/a9b3841d-e977-474e-9e86-8789e470a85a";
-    let bytes = reqwest::get(url).await.unwrap().bytes().await.unwrap();
+    let headers = reader.headers()?;
+    let missing = REQUIRED_COLUMNS
+        .iter()
+        .filter(|column| !headers.iter().any(|header| header == **column))
+        .join(", ");
+    if !missing.is_empty() {
+        return Err(FeedError::Schema(format!("missing columns: {missing}")));
+    }
 
-    csv::ReaderBuilder::new()
-        .has_headers(true)
-        .from_reader(bytes.as_ref())
+    Ok(reader
         .deserialize()
         .filter_map(|record| {
             let record: RawVehicleRecord = record.ok()?;
@@ -54,7 +99,7 @@ async fn fetch_vehicles() -> Vec<RawVehicleRecord> {
                 None
             }
         })
-        .collect()
+        .collect())
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -83,94 +128,147 @@ impl RawVehicleRecord {
     fn sane(&self) -> bool {
         self.line_name != "None"
             && !self.line_name.is_empty()
+            && self.fleet_number != "None"
+            && !self.fleet_number.is_empty()
             && (self.longitude - 16.0).abs() < 10.0
             && (self.latitude - 52.0).abs() < 10.0
     }
 
+    /// Fleet number stays with the vehicle, even when it switches to another line.
     fn id(&self) -> String {
-        format!("{}-{}", self.line_name, self.fleet_number)
+        self.fleet_number.clone()
+    }
+
+    /// Timestamps in the dump are in the local time of Wrocław, without the offset.
+    fn last_update(&self) -> Option<DateTime<Utc>> {
+        let naive = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(&self.last_update, format).ok())?;
+        Some(
+            Warsaw
+                .from_local_datetime(&naive)
+                .earliest()?
+                .with_timezone(&Utc),
+        )
+    }
+
+    fn report(&self) -> Report {
+        Report {
+            id: self.id(),
+            line: self.line_name.clone(),
+            brigade: (self.brigade != "None" && !self.brigade.is_empty())
+                .then(|| self.brigade.clone()),
+            registration: (self.registration_number != "None"
+                && !self.registration_number.is_empty())
+            .then(|| self.registration_number.clone()),
+            position: walkers::lat_lon(self.latitude, self.longitude),
+            time: self.last_update().unwrap_or_else(|| {
+                log::warn!("Unexpected timestamp: '{}'.", self.last_update);
+                Utc::now()
+            }),
+        }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Vehicle {
-    pub line: String,
-    positions: Vec<walkers::Position>,
+/// Tracks vehicles in Wroclaw and keeps a short history.
+pub struct MpkWroclaw {
+    runtime: Option<crate::io::Runtime>,
+    shared: Arc<Shared>,
+    /// Handed over to the background task once the feed is started.
+    source: Option<Source>,
+    recorder: Option<Recorder>,
+    /// Replay paces itself, so the polling interval is kept short whatever the settings say.
+    replaying: bool,
 }
 
-impl Vehicle {
-    fn new(line: String) -> Self {
-        Self {
-            line,
-            positions: Vec::new(),
-        }
+impl MpkWroclaw {
+    pub fn new() -> Self {
+        Self::with_url(URL)
     }
 
-    fn update(&mut self, position: walkers::Position) {
-        if self.positions.last() != Some(&position) {
-            self.positions.push(position);
-        }
-        if self.positions.len() > 10 {
-            self.positions.remove(0);
-        }
+    /// Fetch the dump from somewhere else than the city portal, e.g. `wrowalk_mock`.
+    pub fn with_url(url: impl Into<String>) -> Self {
+        Self::with_source(Source::Http(Downloader::new(url)))
     }
 
-    /// Get the last position of the vehicle.
-    pub fn position(&self) -> walkers::Position {
-        *self.positions.last().unwrap()
+    /// Play back a recording instead of fetching live data.
+    pub fn replay(replay: Replay) -> Self {
+        let mut feed = Self::with_source(Source::Replay(replay));
+        feed.replaying = true;
+        feed.set_settings(feed.settings());
+        feed
     }
 
-    pub fn positions(&self) -> Vec<Position> {
-        self.positions.clone()
+    /// Write every fetched dump to disk, so it can be replayed later.
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
     }
-}
 
-pub struct MpkWroclaw {
-    #[allow(dead_code)]
-    runtime: crate::io::Runtime,
+    /// Use a different clock, e.g. one which does not really sleep. Has to be called before
+    /// the feed is started.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        if let Some(Source::Replay(replay)) = &mut self.source {
+            replay.set_clock(clock.clone());
+        }
+        self.shared = Arc::new(self.shared.replace_clock(clock));
+        self
+    }
 
-    vehicles: Arc<Mutex<HashMap<String, Vehicle>>>,
+    fn with_source(source: Source) -> Self {
+        Self {
+            runtime: None,
+            shared: Default::default(),
+            source: Some(source),
+            recorder: None,
+            replaying: false,
+        }
+    }
 }
 
-/// Tracks vehicles in Wroclaw and keeps a short history.
-impl MpkWroclaw {
-    pub fn new(egui_ctx: egui::Context) -> Self {
-        let vehicles = Arc::new(Mutex::new(HashMap::new()));
+impl Default for MpkWroclaw {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        Self {
-            vehicles: vehicles.clone(),
-            runtime: crate::io::Runtime::new(fetch_continuously(vehicles, egui_ctx)),
+impl VehicleFeed for MpkWroclaw {
+    fn name(&self) -> &str {
+        "MPK Wrocław"
+    }
+
+    fn start(&mut self, egui_ctx: egui::Context) {
+        if let Some(mut source) = self.source.take() {
+            let recorder = self.recorder.take();
+            self.runtime = Some(crate::io::Runtime::new(poll_continuously(
+                self.shared.clone(),
+                egui_ctx,
+                async move |full| fetch_vehicles(&mut source, recorder.as_ref(), full).await,
+            )));
         }
     }
 
-    pub fn vehicles(&self) -> HashMap<String, Vehicle> {
-        self.vehicles.lock().unwrap().clone()
+    fn vehicles(&self) -> HashMap<String, Vehicle> {
+        self.shared.vehicles()
     }
-}
 
-async fn fetch_continuously(
-    vehicles: Arc<Mutex<HashMap<String, Vehicle>>>,
-    egui_ctx: egui::Context,
-) {
-    loop {
-        if !is_app_in_background() {
-            for record in &fetch_vehicles().await {
-                vehicles
-                    .lock()
-                    .unwrap()
-                    .entry(record.id())
-                    .or_insert_with(|| Vehicle::new(record.line_name.clone()))
-                    .update(walkers::lat_lon(record.latitude, record.longitude));
-            }
+    fn status(&self) -> FeedStatus {
+        self.shared.status()
+    }
 
-            log::debug!("Vehicles: {:#?}", vehicles.lock().unwrap());
+    fn settings(&self) -> FeedSettings {
+        self.shared.settings()
+    }
 
-            egui_ctx.request_repaint();
-        } else {
-            log::info!("App is in background, skipping fetch.");
-            vehicles.lock().unwrap().clear();
+    fn set_settings(&mut self, mut settings: FeedSettings) {
+        if self.replaying {
+            // There is no need to wait between the snapshots.
+            settings.polling.interval = Duration::from_millis(100);
         }
+        self.shared.set_settings(settings);
+    }
 
-        sleep(Duration::from_secs(5)).await;
+    fn set_schedule(&mut self, schedule: Arc<Schedule>) {
+        self.shared.set_schedule(schedule);
     }
 }
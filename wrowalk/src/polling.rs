@@ -0,0 +1,125 @@
+//! How often feeds are polled, and how they back off when the server is struggling.
+
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// Polling schedule of a feed, see [`crate::feed::FeedSettings`].
+#[derive(Debug, Clone)]
+pub struct PollingPolicy {
+    /// Delay between polls while the feed is healthy.
+    pub interval: Duration,
+    /// Upper bound of the delay after consecutive failures.
+    pub max_backoff: Duration,
+    /// After a failure, the delay is randomly stretched or shrunk by up to this fraction,
+    /// so that clients which failed together do not retry together.
+    pub jitter: f64,
+}
+
+impl Default for PollingPolicy {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(300),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl PollingPolicy {
+    /// Delay before the next poll, given how many polls in a row have failed and how long
+    /// the server asked us to wait, if at all.
+    pub fn delay(&self, failures: u32, retry_after: Option<Duration>) -> Duration {
+        if failures == 0 {
+            return self.interval;
+        }
+
+        let backoff = self.interval.saturating_mul(2u32.saturating_pow(failures));
+        let jitter = 1.0 + self.jitter * (fastrand::f64() * 2.0 - 1.0);
+        let backoff = backoff.mul_f64(jitter.max(0.0)).min(self.max_backoff);
+
+        retry_after.map_or(backoff, |retry_after| backoff.max(retry_after))
+    }
+}
+
+/// Parse the `Retry-After` header, which is either a number of seconds or an HTTP date.
+pub(crate) fn retry_after(
+    headers: &reqwest::header::HeaderMap,
+    now: DateTime<Utc>,
+) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - now).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+    use super::*;
+
+    #[test]
+    fn healthy_feed_is_polled_at_the_interval() {
+        let policy = PollingPolicy::default();
+        assert_eq!(policy.delay(0, None), policy.interval);
+    }
+
+    #[test]
+    fn backoff_grows_with_jitter_and_is_capped() {
+        let policy = PollingPolicy::default();
+        for _ in 0..100 {
+            let delay = policy.delay(1, None);
+            assert!(delay >= Duration::from_secs(8), "{delay:?}");
+            assert!(delay <= Duration::from_secs(12), "{delay:?}");
+
+            let delay = policy.delay(3, None);
+            assert!(delay >= Duration::from_secs(32), "{delay:?}");
+            assert!(delay <= Duration::from_secs(48), "{delay:?}");
+
+            assert!(policy.delay(30, None) <= policy.max_backoff);
+        }
+    }
+
+    #[test]
+    fn server_asking_to_wait_longer_is_obeyed() {
+        let policy = PollingPolicy::default();
+        let retry_after = Some(Duration::from_secs(60));
+        assert_eq!(policy.delay(1, retry_after), Duration::from_secs(60));
+        assert!(policy.delay(1, Some(Duration::from_secs(1))) >= Duration::from_secs(8));
+    }
+
+    fn headers(retry_after: &'static str) -> HeaderMap {
+        HeaderMap::from_iter([(RETRY_AFTER, HeaderValue::from_static(retry_after))])
+    }
+
+    #[test]
+    fn retry_after_is_read_as_seconds_or_date() {
+        let now = DateTime::parse_from_rfc3339("2026-10-19T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(
+            retry_after(&headers("120"), now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            retry_after(&headers("Mon, 19 Oct 2026 12:02:00 GMT"), now),
+            Some(Duration::from_secs(120))
+        );
+        // Date in the past.
+        assert_eq!(
+            retry_after(&headers("Mon, 19 Oct 2026 11:58:00 GMT"), now),
+            None
+        );
+        assert_eq!(retry_after(&headers("soon"), now), None);
+        assert_eq!(retry_after(&HeaderMap::new(), now), None);
+    }
+}
@@ -0,0 +1,222 @@
+//! Recording raw feed payloads to disk and replaying them later, without the network.
+//!
+//! Each payload is stored in its own file, named after the time it was fetched at, in
+//! milliseconds since the Unix epoch, e.g. `1718000000000.csv`.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::clock::{Clock, SystemClock};
+
+/// Writes payloads into a directory, one file per payload.
+pub struct Recorder {
+    dir: PathBuf,
+    extension: &'static str,
+}
+
+impl Recorder {
+    pub fn new(dir: impl Into<PathBuf>, extension: &'static str) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir, extension })
+    }
+
+    pub fn record(&self, time: DateTime<Utc>, payload: &[u8]) -> io::Result<()> {
+        let path = self
+            .dir
+            .join(format!("{}.{}", time.timestamp_millis(), self.extension));
+        log::debug!("Recording {}.", path.display());
+        std::fs::write(path, payload)
+    }
+}
+
+/// Replay speed from the user, e.g. `WROWALK_REPLAY_SPEED`. Anything which is not a positive
+/// number falls back to the recorded pace.
+pub fn parse_speed(speed: &str) -> f64 {
+    match speed.trim().parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => speed,
+        _ => {
+            log::warn!("Invalid replay speed '{speed}', replaying at the recorded pace.");
+            1.0
+        }
+    }
+}
+
+struct Snapshot {
+    time: DateTime<Utc>,
+    path: PathBuf,
+}
+
+/// Plays back snapshots written by a [`Recorder`], in a loop. Recorded time is mapped onto the
+/// wall clock, so the replayed vehicles look as if they were live.
+pub struct Replay {
+    snapshots: Vec<Snapshot>,
+    speed: f64,
+    next: usize,
+    /// Wall clock and recorded time at which the current loop started.
+    origin: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Replay {
+    /// Open the recording. With `speed` of 2.0, it is played twice as fast as it was recorded.
+    pub fn open(dir: impl AsRef<Path>, speed: f64) -> io::Result<Self> {
+        let mut snapshots: Vec<_> = std::fs::read_dir(dir)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let millis = path.file_stem()?.to_str()?.parse().ok()?;
+                Some(Snapshot {
+                    time: DateTime::from_timestamp_millis(millis)?,
+                    path,
+                })
+            })
+            .collect();
+
+        if snapshots.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "recording has no snapshots",
+            ));
+        }
+
+        snapshots.sort_by_key(|snapshot| snapshot.time);
+
+        Ok(Self {
+            snapshots,
+            speed,
+            next: 0,
+            origin: None,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Use a different clock, e.g. the one of the feed replaying it.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Map recorded time onto the wall clock. Times too far away to be represented, e.g. with
+    /// a very slow replay, are clamped to the start of the loop.
+    pub fn rebase(&self, recorded: DateTime<Utc>) -> DateTime<Utc> {
+        let (real, origin) = self
+            .origin
+            .unwrap_or((self.clock.now(), self.snapshots[0].time));
+        let elapsed = (recorded - origin).num_milliseconds() as f64 / self.speed;
+        TimeDelta::try_milliseconds(elapsed as i64)
+            .and_then(|elapsed| real.checked_add_signed(elapsed))
+            .unwrap_or(real)
+    }
+
+    /// Wait until the next snapshot is due and read it.
+    pub async fn next(&mut self) -> io::Result<Vec<u8>> {
+        if self.next == self.snapshots.len() {
+            log::info!("Replay finished, starting over.");
+            self.next = 0;
+            self.origin = None;
+        }
+
+        let snapshot = &self.snapshots[self.next];
+        let (_, origin) = *self.origin.get_or_insert((self.clock.now(), snapshot.time));
+
+        let due = self.rebase(snapshot.time);
+        if let Ok(delay) = (due - self.clock.now()).to_std() {
+            self.clock.sleep(delay).await;
+        }
+
+        log::debug!(
+            "Replaying {}, {} into the recording.",
+            snapshot.path.display(),
+            snapshot.time - origin
+        );
+        let payload = std::fs::read(&snapshot.path)?;
+        self.next += 1;
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClock;
+
+    /// Replay of payloads recorded into a temporary directory named after the test.
+    fn replay(test: &str, recorded: &[(DateTime<Utc>, &str)], speed: f64) -> Replay {
+        let dir = std::env::temp_dir().join(format!("wrowalk-{test}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let recorder = Recorder::new(&dir, "csv").unwrap();
+        for (time, payload) in recorded {
+            recorder.record(*time, payload.as_bytes()).unwrap();
+        }
+        Replay::open(&dir, speed).unwrap()
+    }
+
+    #[test]
+    fn recording_is_replayed_faster_and_in_a_loop() {
+        let recorded = DateTime::from_timestamp(1_760_000_000, 0).unwrap();
+        let mut replay = replay(
+            "loop",
+            &[
+                (recorded + TimeDelta::seconds(20), "c"),
+                (recorded, "a"),
+                (recorded + TimeDelta::seconds(10), "b"),
+            ],
+            2.0,
+        );
+        let start = DateTime::from_timestamp(1_770_000_000, 0).unwrap();
+        let clock = TestClock::new(start);
+        replay.set_clock(clock.clone());
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let mut next = || {
+            let payload = runtime.block_on(replay.next()).unwrap();
+            (String::from_utf8(payload).unwrap(), clock.now() - start)
+        };
+
+        assert_eq!(next(), ("a".to_string(), TimeDelta::zero()));
+        assert_eq!(next(), ("b".to_string(), TimeDelta::seconds(5)));
+        assert_eq!(next(), ("c".to_string(), TimeDelta::seconds(10)));
+        // Starts over right away.
+        assert_eq!(next(), ("a".to_string(), TimeDelta::seconds(10)));
+        assert_eq!(
+            replay.rebase(recorded + TimeDelta::seconds(10)),
+            start + TimeDelta::seconds(15)
+        );
+    }
+
+    #[test]
+    fn invalid_speeds_fall_back_to_recorded_pace() {
+        assert_eq!(parse_speed("2.5"), 2.5);
+        assert_eq!(parse_speed("0"), 1.0);
+        assert_eq!(parse_speed("-2"), 1.0);
+        assert_eq!(parse_speed("NaN"), 1.0);
+        assert_eq!(parse_speed("inf"), 1.0);
+        assert_eq!(parse_speed("fast"), 1.0);
+    }
+
+    #[test]
+    fn rebasing_does_not_overflow() {
+        let recorded = DateTime::from_timestamp(1_760_000_000, 0).unwrap();
+        for speed in [1e-300, 0.0, -2.0] {
+            let replay = replay(
+                "overflow",
+                &[(recorded, "a"), (recorded + TimeDelta::days(1), "b")],
+                speed,
+            );
+            replay.rebase(recorded + TimeDelta::days(1));
+        }
+    }
+
+    #[test]
+    fn empty_recording_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("wrowalk-empty-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(Replay::open(&dir, 1.0).is_err());
+    }
+}
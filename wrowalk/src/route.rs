@@ -0,0 +1,100 @@
+//! Overlay of the route of a line, as planned in the timetable.
+
+use egui::{vec2, Align2, Color32, FontId, Pos2, Shape, Stroke};
+use walkers::{Plugin, Position};
+
+use crate::gtfs::Schedule;
+
+/// Distance between direction arrows, in points.
+const ARROW_SPACING: f32 = 120.0;
+
+/// Draws shapes of all the variants of a line, in both directions, with arrows showing which
+/// way vehicles go and labels at the termini.
+pub(crate) struct Route {
+    shapes: Vec<Vec<Position>>,
+    termini: Vec<(Position, String)>,
+    color: Color32,
+}
+
+impl Route {
+    /// Route of the line, if the timetable knows it.
+    pub fn new(schedule: &Schedule, line: &str) -> Option<Self> {
+        let route = schedule.route_by_name(line)?;
+        let mut shapes = Vec::new();
+        let mut termini: Vec<(Position, String)> = Vec::new();
+
+        for (trip, shape) in schedule.shapes_of(&route.id) {
+            if let (Some(last), Some(headsign)) = (shape.last(), &trip.headsign) {
+                if !termini.iter().any(|(_, other)| other == headsign) {
+                    termini.push((*last, headsign.clone()));
+                }
+            }
+            shapes.push(shape.to_vec());
+        }
+
+        Some(Self {
+            shapes,
+            termini,
+            color: route.category().color(),
+        })
+    }
+}
+
+impl Plugin for Route {
+    fn run(
+        self: Box<Self>,
+        ui: &mut egui::Ui,
+        _response: &egui::Response,
+        projector: &walkers::Projector,
+        _map_memory: &walkers::MapMemory,
+    ) {
+        let painter = ui.painter();
+        let stroke = Stroke::new(4.0, self.color.gamma_multiply(0.7));
+
+        for shape in &self.shapes {
+            let points: Vec<Pos2> = shape
+                .iter()
+                .map(|position| projector.project(*position).to_pos2())
+                .collect();
+            painter.add(Shape::line(points.clone(), stroke));
+
+            // Arrows are placed along the line, the first one half of the spacing in.
+            let mut until_arrow = ARROW_SPACING / 2.0;
+            for segment in points.windows(2) {
+                let (from, to) = (segment[0], segment[1]);
+                let length = from.distance(to);
+                let mut along = until_arrow;
+                while along < length {
+                    arrow(
+                        painter,
+                        from + (to - from) * (along / length),
+                        (to - from) / length,
+                    );
+                    along += ARROW_SPACING;
+                }
+                until_arrow = along - length;
+            }
+        }
+
+        for (position, headsign) in &self.termini {
+            let position = projector.project(*position).to_pos2();
+            let galley =
+                painter.layout_no_wrap(headsign.clone(), FontId::proportional(12.), Color32::WHITE);
+            let rect = Align2::CENTER_BOTTOM
+                .anchor_size(position - vec2(0., 8.), galley.size())
+                .expand(2.);
+            painter.rect_filled(rect, 1., self.color);
+            painter.galley(rect.shrink(2.).min, galley, Color32::WHITE);
+        }
+    }
+}
+
+/// Chevron pointing in the `direction`, which is a unit vector.
+fn arrow(painter: &egui::Painter, at: Pos2, direction: egui::Vec2) {
+    let back = at - direction * 5.0;
+    let side = direction.rot90() * 4.0;
+    painter.add(Shape::line(
+        vec![back + side, at, back - side],
+        Stroke::new(2.0, Color32::WHITE),
+    ));
+}
@@ -0,0 +1,225 @@
+//! Finding lines, vehicles and stops by name, the way people type them on a phone: in any
+//! case and often without Polish letters.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    gtfs::{Schedule, Stop},
+    vehicle::{Vehicle, VehicleKey},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Hit {
+    Line(String),
+    Vehicle {
+        key: VehicleKey,
+        line: String,
+        registration: Option<String>,
+    },
+    Stop {
+        id: String,
+        name: String,
+        code: Option<String>,
+    },
+}
+
+impl Hit {
+    /// How the hit is shown on the list of results.
+    pub fn text(&self) -> String {
+        match self {
+            Hit::Line(line) => format!("Line {line}"),
+            Hit::Vehicle {
+                key,
+                line,
+                registration: Some(registration),
+            } => format!("Vehicle {} ({registration}) on line {line}", key.id),
+            Hit::Vehicle { key, line, .. } => format!("Vehicle {} on line {line}", key.id),
+            Hit::Stop {
+                name,
+                code: Some(code),
+                ..
+            } => format!("Stop {name} ({code})"),
+            Hit::Stop { name, .. } => format!("Stop {name}"),
+        }
+    }
+}
+
+/// Lowercase text without diacritics, so "Świdnicka" can be found as "swidnicka".
+pub fn fold(text: &str) -> String {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'ą' => 'a',
+            'ć' => 'c',
+            'ę' => 'e',
+            'ł' => 'l',
+            'ń' => 'n',
+            'ó' => 'o',
+            'ś' => 's',
+            'ź' | 'ż' => 'z',
+            c => c,
+        })
+        .collect()
+}
+
+/// How well the text matches the folded query, the lower the better, if at all.
+fn score(query: &str, text: &str) -> Option<u8> {
+    let text = fold(text);
+    if text == query {
+        Some(0)
+    } else if text.starts_with(query) {
+        Some(1)
+    } else if text.contains(query) {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Lines, vehicles (by fleet or registration number) and stops matching the query, the best
+/// matches first.
+pub fn search(
+    query: &str,
+    schedule: &Schedule,
+    vehicles: &HashMap<VehicleKey, Vehicle>,
+    limit: usize,
+) -> Vec<Hit> {
+    let query = fold(query.trim());
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let lines: BTreeSet<&str> = schedule
+        .routes
+        .values()
+        .map(|route| route.short_name.as_str())
+        .chain(vehicles.values().map(|vehicle| vehicle.line.as_str()))
+        .collect();
+
+    let lines = lines
+        .into_iter()
+        .filter_map(|line| Some((score(&query, line)?, Hit::Line(line.to_string()))));
+
+    let vehicles = vehicles.iter().filter_map(|(key, vehicle)| {
+        let score = std::iter::once(&key.id)
+            .chain(&vehicle.registration)
+            .filter_map(|text| score(&query, text))
+            .min()?;
+        Some((
+            score,
+            Hit::Vehicle {
+                key: key.clone(),
+                line: vehicle.line.clone(),
+                registration: vehicle.registration.clone(),
+            },
+        ))
+    });
+
+    let stops = schedule
+        .stops
+        .values()
+        .filter_map(|stop| Some((score(&query, &stop.name)?, stop_hit(stop))));
+
+    let mut hits: Vec<(u8, Hit)> = lines.chain(vehicles).chain(stops).collect();
+    hits.sort();
+    hits.into_iter().take(limit).map(|(_, hit)| hit).collect()
+}
+
+/// Favourite stops, by name, to be listed while nothing is typed. Ones which are not in the
+/// timetable, or not yet, are skipped.
+pub fn favourites(schedule: &Schedule, favourites: &BTreeSet<String>) -> Vec<Hit> {
+    let mut hits: Vec<Hit> = favourites
+        .iter()
+        .filter_map(|id| Some(stop_hit(schedule.stops.get(id.as_str())?)))
+        .collect();
+    hits.sort_by_key(Hit::text);
+    hits
+}
+
+fn stop_hit(stop: &Stop) -> Hit {
+    Hit::Stop {
+        id: stop.id.to_string(),
+        name: stop.name.clone(),
+        code: stop.code.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn polish_letters_are_folded() {
+        assert_eq!(fold("Świdnicka"), "swidnicka");
+        assert_eq!(fold("ŻÓŁĆ gęślą jaźń"), "zolc gesla jazn");
+        assert_eq!(fold("Rynek"), fold("rynek"));
+    }
+
+    #[test]
+    fn stops_are_found_without_diacritics() {
+        let schedule = testing::schedule();
+
+        let hits = search("dworzec glowny", &schedule, &HashMap::new(), 10);
+        assert_eq!(
+            hits,
+            vec![Hit::Stop {
+                id: "1".to_string(),
+                name: "Dworzec Główny".to_string(),
+                code: Some("10001".to_string()),
+            }]
+        );
+
+        let hits = search("DOMINIK", &schedule, &HashMap::new(), 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].text(), "Stop Galeria Dominikańska (10002)");
+    }
+
+    #[test]
+    fn exact_matches_come_first() {
+        let schedule = testing::schedule();
+
+        let hits = search("1", &schedule, &HashMap::new(), 10);
+        assert_eq!(hits[0], Hit::Line("1".to_string()));
+        assert!(hits.contains(&Hit::Line("145".to_string())));
+
+        assert!(search("  ", &schedule, &HashMap::new(), 10).is_empty());
+        assert_eq!(search("1", &schedule, &HashMap::new(), 1).len(), 1);
+    }
+
+    #[test]
+    fn favourites_are_listed_by_name() {
+        let schedule = testing::schedule();
+        let starred = BTreeSet::from(["3".to_string(), "1".to_string(), "404".to_string()]);
+
+        let names: Vec<_> = favourites(&schedule, &starred)
+            .iter()
+            .map(Hit::text)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Stop Dworzec Główny (10001)",
+                "Stop Plac Grunwaldzki (10003)"
+            ]
+        );
+    }
+
+    #[test]
+    fn same_fleet_number_in_two_feeds_is_found_twice() {
+        let vehicles: HashMap<VehicleKey, Vehicle> = (0..2)
+            .map(|feed| {
+                let key = VehicleKey {
+                    feed,
+                    id: "2401".to_string(),
+                };
+                (key, Vehicle::new("1".to_string()))
+            })
+            .collect();
+
+        let hits = search("2401", &testing::schedule(), &vehicles, 10);
+
+        assert_eq!(hits.len(), 2);
+        assert_ne!(hits[0], hits[1]);
+    }
+}
@@ -0,0 +1,166 @@
+//! User settings and the state of the map, kept across restarts by the `eframe` storage.
+//!
+//! Fields missing from what was saved get their defaults, so adding one does not need a new
+//! version. [`VERSION`] goes up only when the meaning of the saved fields changes, together
+//! with a step in [`migrate`].
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{animation::Animation, filter::LineFilter, tiles::Provider};
+
+const KEY: &str = "wrowalk";
+
+/// Version of the settings written by this build.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub provider: Provider,
+    /// Zoom of the map, if it was ever changed.
+    pub zoom: Option<f64>,
+    /// Latitude and longitude of the centre of the map, unless it followed my position.
+    pub center: Option<(f64, f64)>,
+    pub line_filter: LineFilter,
+    /// Line whose route is shown.
+    pub selected_line: Option<String>,
+    /// Ids of favourite stops.
+    pub favourites: BTreeSet<String>,
+    pub snap_to_routes: bool,
+    pub animation: Animation,
+    pub stops_min_zoom: f64,
+    /// Vehicles which did not report their position for this many minutes are dimmed.
+    pub stale_after_minutes: i64,
+    /// Feeds are polled that often while they are healthy, in seconds.
+    pub poll_interval_seconds: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            provider: Provider::OpenStreetMap,
+            zoom: None,
+            center: None,
+            line_filter: Default::default(),
+            selected_line: None,
+            favourites: BTreeSet::new(),
+            snap_to_routes: true,
+            animation: Default::default(),
+            stops_min_zoom: 15.0,
+            stale_after_minutes: 2,
+            poll_interval_seconds: 5,
+        }
+    }
+}
+
+/// Settings saved earlier, brought up to the current version, if there are any.
+pub fn load(storage: &dyn eframe::Storage) -> Option<Settings> {
+    #[derive(Deserialize)]
+    struct Versioned {
+        version: u32,
+    }
+
+    let version = eframe::get_value::<Versioned>(storage, KEY)?.version;
+    let settings = migrate(version, storage);
+    if settings.is_none() {
+        log::warn!("Ignoring settings of version {version}, this build reads {VERSION}.");
+    }
+    settings
+}
+
+pub fn save(storage: &mut dyn eframe::Storage, settings: &Settings) {
+    eframe::set_value(storage, KEY, settings);
+}
+
+/// Read the settings of given version as the current ones. Versions written by a newer build
+/// are not understood.
+fn migrate(version: u32, storage: &dyn eframe::Storage) -> Option<Settings> {
+    match version {
+        VERSION => eframe::get_value(storage, KEY),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MemoryStorage(HashMap<String, String>);
+
+    impl eframe::Storage for MemoryStorage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_string(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    #[test]
+    fn nothing_is_loaded_from_empty_storage() {
+        assert_eq!(load(&MemoryStorage::default()), None);
+    }
+
+    #[test]
+    fn settings_survive_a_restart() {
+        let mut filter = LineFilter::default();
+        filter.only("33", ["1", "33"]);
+        let saved = Settings {
+            zoom: Some(14.5),
+            center: Some((51.11, 17.03)),
+            line_filter: filter,
+            selected_line: Some("33".to_string()),
+            favourites: BTreeSet::from(["10001".to_string()]),
+            snap_to_routes: false,
+            animation: Animation::DeadReckoning,
+            stale_after_minutes: 5,
+            poll_interval_seconds: 15,
+            ..Default::default()
+        };
+
+        let mut storage = MemoryStorage::default();
+        save(&mut storage, &saved);
+
+        assert_eq!(load(&storage), Some(saved));
+    }
+
+    #[test]
+    fn missing_fields_get_defaults() {
+        let mut storage = MemoryStorage::default();
+        storage.0.insert(
+            "wrowalk".to_string(),
+            format!("(version: {VERSION}, zoom: Some(12.0))"),
+        );
+
+        let loaded = load(&storage).unwrap();
+        assert_eq!(loaded.zoom, Some(12.0));
+        assert_eq!(
+            loaded,
+            Settings {
+                zoom: Some(12.0),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn settings_of_newer_version_are_ignored() {
+        let mut storage = MemoryStorage::default();
+        storage.0.insert(
+            "wrowalk".to_string(),
+            format!("(version: {}, future: true)", VERSION + 1),
+        );
+
+        assert_eq!(load(&storage), None);
+    }
+}
@@ -0,0 +1,67 @@
+//! Public transport stops, drawn from the timetable.
+
+use egui::{Color32, Stroke};
+use walkers::Plugin;
+
+use crate::gtfs::Schedule;
+
+/// Stops are tappable within this distance, in points.
+const TAP_RADIUS: f32 = 12.0;
+
+/// Draws stops, once the map is zoomed in enough for them not to clutter it, and selects the
+/// tapped one.
+pub(crate) struct Stops<'a> {
+    pub schedule: &'a Schedule,
+    pub min_zoom: f64,
+    pub selected: &'a mut Option<String>,
+}
+
+impl Plugin for Stops<'_> {
+    fn run(
+        self: Box<Self>,
+        ui: &mut egui::Ui,
+        response: &egui::Response,
+        projector: &walkers::Projector,
+        map_memory: &walkers::MapMemory,
+    ) {
+        if map_memory.zoom() < self.min_zoom {
+            return;
+        }
+
+        let tapped = response
+            .clicked()
+            .then(|| response.interact_pointer_pos())
+            .flatten();
+        let mut closest: Option<(f32, &str)> = None;
+
+        for stop in self.schedule.stops.values() {
+            let position = projector.project(stop.position).to_pos2();
+            if !response.rect.expand(TAP_RADIUS).contains(position) {
+                continue;
+            }
+
+            let selected = self.selected.as_deref() == Some(&*stop.id);
+            ui.painter().circle(
+                position,
+                if selected { 7.0 } else { 4.0 },
+                if selected {
+                    Color32::YELLOW
+                } else {
+                    Color32::WHITE
+                },
+                Stroke::new(1.5, Color32::from_gray(40)),
+            );
+
+            if let Some(tapped) = tapped {
+                let distance = tapped.distance(position);
+                if distance < TAP_RADIUS && closest.is_none_or(|(closest, _)| distance < closest) {
+                    closest = Some((distance, &stop.id));
+                }
+            }
+        }
+
+        if let Some((_, id)) = closest {
+            *self.selected = Some(id.to_string());
+        }
+    }
+}
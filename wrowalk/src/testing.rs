@@ -0,0 +1,19 @@
+//! Fixtures of the unit tests, shared with the integration tests.
+
+use crate::{
+    clock::{Clock, Sleep},
+    gtfs::Schedule,
+};
+
+#[path = "../tests/timetable/mod.rs"]
+pub(crate) mod timetable;
+
+#[path = "../tests/clock/mod.rs"]
+mod clock;
+
+pub(crate) use clock::TestClock;
+
+/// The timetable made of [`timetable::FILES`].
+pub(crate) fn schedule() -> Schedule {
+    Schedule::parse(&timetable::zip(timetable::FILES)).unwrap()
+}
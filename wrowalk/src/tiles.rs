@@ -1,9 +1,10 @@
 use std::collections::BTreeMap;
 
 use egui::Context;
+use serde::{Deserialize, Serialize};
 use walkers::{HttpOptions, HttpTiles, Tiles};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Provider {
     OpenStreetMap,
     Geoportal,
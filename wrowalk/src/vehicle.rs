@@ -0,0 +1,217 @@
+//! Model of a tracked vehicle, shared by all feeds.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use walkers::Position;
+
+use crate::{delay::TripMatch, direction::Direction, geo};
+
+/// Fixes closer to each other than that are indistinguishable from GPS noise, in meters.
+const MIN_DISPLACEMENT: f64 = 15.0;
+
+/// Nothing in the city moves faster, so such jumps are glitches, in km/h.
+const MAX_SPEED: f64 = 120.0;
+
+/// Speed is averaged over this much of the recent history.
+const SPEED_WINDOW: TimeDelta = TimeDelta::seconds(60);
+
+/// Identifies a vehicle among the vehicles of all feeds. Operators number their fleets on
+/// their own, so the same id might come from two feeds.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VehicleKey {
+    /// Index of the feed in the app.
+    pub feed: usize,
+    /// Id within the feed, e.g. the fleet number.
+    pub id: String,
+}
+
+/// Position of the vehicle at a given time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fix {
+    pub time: DateTime<Utc>,
+    /// As reported by the feed.
+    pub position: Position,
+    /// Moved onto the route of the line, if it was close enough.
+    pub snapped: Option<Position>,
+}
+
+impl Fix {
+    /// Best guess of where the vehicle was: snapped onto its route if possible, as reported
+    /// otherwise.
+    pub fn best(&self) -> Position {
+        self.snapped.unwrap_or(self.position)
+    }
+}
+
+/// Vehicle switching to another line, which trams often do during the day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineChange {
+    pub time: DateTime<Utc>,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Vehicle {
+    /// Line the vehicle currently serves.
+    pub line: String,
+    /// Brigade, that is the daily duty of the vehicle, if the feed tells it.
+    pub brigade: Option<String>,
+    /// Registration plate, if the vehicle has one and the feed tells it.
+    pub registration: Option<String>,
+    fixes: Vec<Fix>,
+    line_changes: Vec<LineChange>,
+    trip: Option<TripMatch>,
+    direction: Option<Direction>,
+}
+
+impl Vehicle {
+    pub(crate) fn new(line: String) -> Self {
+        Self {
+            line,
+            brigade: None,
+            registration: None,
+            fixes: Vec::new(),
+            line_changes: Vec::new(),
+            trip: None,
+            direction: None,
+        }
+    }
+
+    pub(crate) fn update(&mut self, line: &str, fix: Fix) {
+        if self.fixes.last().is_some_and(|last| fix.time < last.time) {
+            // Feeds often repeat the same record, or even go back in time.
+            return;
+        }
+
+        if self.line != line {
+            self.line_changes.push(LineChange {
+                time: fix.time,
+                from: std::mem::replace(&mut self.line, line.to_string()),
+                to: line.to_string(),
+            });
+            if self.line_changes.len() > 50 {
+                self.line_changes.remove(0);
+            }
+        }
+
+        match self.fixes.last_mut() {
+            // Standing still, but still alive.
+            Some(last) if last.position == fix.position => *last = fix,
+            _ => self.fixes.push(fix),
+        }
+        if self.fixes.len() > 10 {
+            self.fixes.remove(0);
+        }
+    }
+
+    /// Get the last position of the vehicle, see [`Fix::best`].
+    pub fn position(&self) -> Position {
+        self.fixes.last().unwrap().best()
+    }
+
+    pub fn positions(&self) -> Vec<Position> {
+        self.fixes.iter().map(Fix::best).collect()
+    }
+
+    /// The same vehicle, with positions as reported by the feed.
+    pub fn unsnapped(mut self) -> Self {
+        for fix in &mut self.fixes {
+            fix.snapped = None;
+        }
+        self
+    }
+
+    /// Timestamped positions, the oldest first.
+    pub fn fixes(&self) -> &[Fix] {
+        &self.fixes
+    }
+
+    /// Trip of the timetable the vehicle is on, as far as it can be told.
+    pub fn trip(&self) -> Option<&TripMatch> {
+        self.trip.as_ref()
+    }
+
+    pub(crate) fn set_trip(&mut self, trip: Option<TripMatch>) {
+        self.trip = trip;
+    }
+
+    /// Destination and the next stop of the vehicle, as far as it can be told.
+    pub fn direction(&self) -> Option<&Direction> {
+        self.direction.as_ref()
+    }
+
+    pub(crate) fn set_direction(&mut self, direction: Option<Direction>) {
+        self.direction = direction;
+    }
+
+    /// Changes of the line, the oldest first.
+    pub fn line_changes(&self) -> &[LineChange] {
+        &self.line_changes
+    }
+
+    /// Lines served by the vehicle, in order, the current one last.
+    pub fn lines_served(&self) -> Vec<&str> {
+        self.line_changes
+            .first()
+            .map(|change| change.from.as_str())
+            .into_iter()
+            .chain(self.line_changes.iter().map(|change| change.to.as_str()))
+            .chain(self.line_changes.is_empty().then_some(self.line.as_str()))
+            .collect()
+    }
+
+    /// Average speed over the last minute, in km/h. Implausible jumps are ignored.
+    pub fn speed(&self) -> Option<f64> {
+        let last = self.fixes.last()?;
+        let mut distance = 0.0;
+        let mut elapsed = 0.0;
+
+        for fix in self.fixes.windows(2).rev() {
+            let [from, to] = fix else { unreachable!() };
+            if last.time - to.time > SPEED_WINDOW {
+                break;
+            }
+
+            let seconds = (to.time - from.time).num_milliseconds() as f64 / 1000.0;
+            let meters = geo::distance(from.best(), to.best());
+            if seconds <= 0.0 || meters / seconds * 3.6 > MAX_SPEED {
+                continue;
+            }
+
+            distance += meters;
+            elapsed += seconds;
+        }
+
+        (elapsed > 0.0).then(|| distance / elapsed * 3.6)
+    }
+
+    /// Direction the vehicle is heading to, in degrees clockwise from the north. It is kept
+    /// while the vehicle stands still, so it is known even at stops.
+    pub fn bearing(&self) -> Option<f64> {
+        let last = self.fixes.last()?.best();
+        self.fixes
+            .iter()
+            .rev()
+            .find(|fix| geo::distance(fix.best(), last) >= MIN_DISPLACEMENT)
+            .map(|fix| geo::bearing(fix.best(), last))
+    }
+
+    /// When the vehicle reported its position for the last time.
+    pub fn last_seen(&self) -> DateTime<Utc> {
+        self.fixes.last().unwrap().time
+    }
+
+    /// How long ago the vehicle reported its position for the last time.
+    pub fn age(&self, now: DateTime<Utc>) -> TimeDelta {
+        now - self.last_seen()
+    }
+}
+
+/// Human friendly form of [`Vehicle::age`], e.g. "last seen 42 s ago".
+pub fn last_seen_ago(age: TimeDelta) -> String {
+    match age.num_seconds() {
+        seconds @ ..60 => format!("last seen {} s ago", seconds.max(0)),
+        seconds @ ..3600 => format!("last seen {} min ago", seconds / 60),
+        seconds => format!("last seen {} h ago", seconds / 3600),
+    }
+}
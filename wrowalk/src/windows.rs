@@ -1,5 +1,25 @@
-use crate::MyApp;
-use egui::{Align2, ComboBox, Image, Response, RichText, Ui, Window};
+use crate::{
+    animation::Animation,
+    category::Category,
+    delay::{self, Punctuality},
+    departures::{departures, Departure},
+    feed::FeedStatus,
+    filter::LineFilter,
+    gtfs::{self, Gtfs, LoadStatus, Schedule},
+    markers,
+    search::Hit,
+    vehicle::{self, Vehicle, VehicleKey},
+    MyApp,
+};
+use chrono::{Local, TimeDelta, Utc};
+use egui::{
+    vec2, Align2, ComboBox, Grid, Image, Response, RichText, ScrollArea, Sense, Slider, TextEdit,
+    Ui, Window,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    time::Duration,
+};
 use walkers::{sources::Attribution, MapMemory};
 
 pub fn acknowledge(app: &mut MyApp, ui: &Ui, attributions: Vec<Attribution>) {
@@ -9,7 +29,13 @@ pub fn acknowledge(app: &mut MyApp, ui: &Ui, attributions: Vec<Attribution>) {
         .title_bar(false)
         .anchor(Align2::LEFT_TOP, [10., 10.])
         .show(ui.ctx(), |ui| {
-            ui.label(format!("Tracking {} vehicles.", app.positions().len()));
+            let vehicles = app.vehicles();
+            ui.label(format!("Tracking {} vehicles.", vehicles.len()));
+            for (n, feed) in app.feeds.iter().enumerate() {
+                let count = vehicles.keys().filter(|key| key.feed == n).count();
+                feed_status(ui, feed.name(), count, &feed.status());
+            }
+            timetable_status(ui, &app.gtfs);
 
             ComboBox::from_id_salt("Tile Provider")
                 .selected_text(format!("{:?}", app.selected_provider))
@@ -19,6 +45,57 @@ pub fn acknowledge(app: &mut MyApp, ui: &Ui, attributions: Vec<Attribution>) {
                     }
                 });
 
+            ui.add(Slider::new(&mut app.stops_min_zoom, 10.0..=19.0).text("Stops from zoom"));
+
+            let schedule = app.gtfs.schedule();
+            let mut lines: Vec<&str> = schedule
+                .routes
+                .values()
+                .map(|route| route.short_name.as_str())
+                .collect();
+            gtfs::sort_lines(&mut lines);
+            ComboBox::from_id_salt("Route")
+                .selected_text(format!(
+                    "Route: {}",
+                    app.selected_line.as_deref().unwrap_or("none")
+                ))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut app.selected_line, None, "none");
+                    for line in lines {
+                        ui.selectable_value(&mut app.selected_line, Some(line.to_string()), line);
+                    }
+                });
+
+            ui.checkbox(&mut app.snap_to_routes, "Snap to routes");
+
+            let mut stale_after = app.stale_after.num_minutes();
+            if ui
+                .add(Slider::new(&mut stale_after, 1..=15).text("Dim after, minutes"))
+                .changed()
+            {
+                app.stale_after = TimeDelta::minutes(stale_after);
+            }
+
+            let mut poll_interval = app.poll_interval.as_secs();
+            if ui
+                .add(Slider::new(&mut poll_interval, 1..=60).text("Poll every, seconds"))
+                .changed()
+            {
+                app.set_poll_interval(Duration::from_secs(poll_interval));
+            }
+
+            ComboBox::from_id_salt("Animation")
+                .selected_text(format!("{:?}", app.animator.mode))
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        Animation::Off,
+                        Animation::Interpolate,
+                        Animation::DeadReckoning,
+                    ] {
+                        ui.selectable_value(&mut app.animator.mode, mode, format!("{mode:?}"));
+                    }
+                });
+
             for attribution in attributions {
                 ui.horizontal(|ui| {
                     if let Some(logo) = attribution.logo_light {
@@ -30,12 +107,327 @@ pub fn acknowledge(app: &mut MyApp, ui: &Ui, attributions: Vec<Attribution>) {
         });
 }
 
+/// What the symbols on the map stand for.
+pub fn legend(ui: &Ui) {
+    Window::new("Legend")
+        .collapsible(false)
+        .resizable(false)
+        .title_bar(false)
+        .anchor(Align2::RIGHT_TOP, [-10., 10.])
+        .show(ui.ctx(), |ui| {
+            for category in Category::ALL {
+                ui.horizontal(|ui| {
+                    let (rect, _) = ui.allocate_exact_size(
+                        vec2(markers::SIZE, markers::SIZE) * 1.3,
+                        Sense::hover(),
+                    );
+                    markers::symbol(ui.painter(), rect.center(), category, "", 1.0);
+                    ui.label(category.name());
+                });
+            }
+
+            ui.separator();
+            for (punctuality, name) in [
+                (Punctuality::Early, "Early"),
+                (Punctuality::OnTime, "On time"),
+                (Punctuality::Late, "Late"),
+            ] {
+                ui.colored_label(markers::punctuality_color(punctuality), name);
+            }
+        });
+}
+
+/// Lines currently on the map, by category, to choose which of them are shown.
+pub fn lines(ui: &Ui, filter: &mut LineFilter, lines: &BTreeMap<Category, Vec<String>>) {
+    let seen = || lines.values().flatten().map(String::as_str);
+
+    Window::new(if filter.is_active() {
+        "Lines (filtered)"
+    } else {
+        "Lines"
+    })
+    .id(egui::Id::new("Lines"))
+    .default_open(false)
+    .resizable(false)
+    .anchor(Align2::RIGHT_CENTER, [-10., 0.])
+    .show(ui.ctx(), |ui| {
+        ui.horizontal(|ui| {
+            if ui.button("All").clicked() {
+                filter.all();
+            }
+            if ui.button("None").clicked() {
+                filter.none(seen());
+            }
+        });
+
+        ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+            for (category, lines) in lines {
+                ui.separator();
+                ui.strong(category.name());
+                for line in lines {
+                    ui.horizontal(|ui| {
+                        let mut shown = filter.shows(line);
+                        if ui.checkbox(&mut shown, line).changed() {
+                            filter.set(line, shown);
+                        }
+                        if ui.small_button("only this").clicked() {
+                            filter.only(line, seen());
+                        }
+                    });
+                }
+            }
+        });
+    });
+}
+
+/// Search box, with the results below it. Returns the picked one.
+pub fn search(ui: &Ui, query: &mut String, hits: &[Hit]) -> Option<Hit> {
+    let mut picked = None;
+
+    Window::new("Search")
+        .collapsible(false)
+        .resizable(false)
+        .title_bar(false)
+        .anchor(Align2::CENTER_TOP, [0., 10.])
+        .show(ui.ctx(), |ui| {
+            let response =
+                ui.add(TextEdit::singleline(query).hint_text("\u{e8b6} Line, vehicle or stop"));
+            let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+            for (n, hit) in hits.iter().enumerate() {
+                if ui.selectable_label(false, hit.text()).clicked() || (enter && n == 0) {
+                    picked = Some(hit.clone());
+                }
+            }
+            if !query.trim().is_empty() && hits.is_empty() {
+                ui.weak("Nothing found.");
+            }
+        });
+
+    picked
+}
+
+/// Name, code, lines and departures of the selected stop. Tapping a line shows its route,
+/// tapping the star adds the stop to the favourites.
+pub fn stop(
+    ui: &Ui,
+    schedule: &Schedule,
+    vehicles: &HashMap<VehicleKey, Vehicle>,
+    selected: &mut Option<String>,
+    selected_line: &mut Option<String>,
+    favourites: &mut BTreeSet<String>,
+) {
+    let Some(stop) = selected
+        .as_ref()
+        .and_then(|id| schedule.stops.get(id.as_str()))
+    else {
+        return;
+    };
+
+    let mut open = true;
+    Window::new(&stop.name)
+        .id(egui::Id::new("Stop"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::RIGHT_BOTTOM, [-10., -10.])
+        .show(ui.ctx(), |ui| {
+            ui.horizontal(|ui| {
+                let favourite = favourites.contains(&*stop.id);
+                if ui
+                    .selectable_label(favourite, if favourite { "\u{e838}" } else { "\u{e83a}" })
+                    .on_hover_text("Favourite")
+                    .clicked()
+                {
+                    if favourite {
+                        favourites.remove(&*stop.id);
+                    } else {
+                        favourites.insert(stop.id.to_string());
+                    }
+                }
+                if let Some(code) = &stop.code {
+                    ui.label(format!("Stop {code}"));
+                }
+            });
+            ui.horizontal_wrapped(|ui| {
+                ui.label("Lines:");
+                for line in schedule.lines_at(&stop.id) {
+                    let is_selected = selected_line.as_deref() == Some(line);
+                    if ui.selectable_label(is_selected, line).clicked() {
+                        *selected_line = (!is_selected).then(|| line.to_string());
+                    }
+                }
+            });
+
+            ui.separator();
+            departures_board(ui, &departures(schedule, &stop.id, vehicles, Utc::now(), 3));
+        });
+
+    if !open {
+        *selected = None;
+    }
+}
+
+/// Everything known about the selected vehicle.
+pub fn vehicle(
+    ui: &Ui,
+    schedule: &Schedule,
+    vehicles: &HashMap<VehicleKey, Vehicle>,
+    selected: &mut Option<VehicleKey>,
+) {
+    let Some((key, vehicle)) = selected
+        .as_ref()
+        .and_then(|key| vehicles.get_key_value(key))
+    else {
+        return;
+    };
+
+    let mut open = true;
+    Window::new(format!("Line {}", vehicle.line))
+        .id(egui::Id::new("Vehicle"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_BOTTOM, [0., -10.])
+        .show(ui.ctx(), |ui| {
+            Grid::new("Vehicle details").show(ui, |ui| {
+                let mut row = |name: &str, value: String| {
+                    ui.label(name);
+                    ui.strong(value);
+                    ui.end_row();
+                };
+
+                row("Fleet number", key.id.clone());
+                if let Some(registration) = &vehicle.registration {
+                    row("Registration", registration.clone());
+                }
+                if let Some(brigade) = &vehicle.brigade {
+                    row("Brigade", brigade.clone());
+                }
+                if let Some(direction) = vehicle.direction() {
+                    row("Heading to", direction.headsign.clone());
+                    if let Some(stop) = direction
+                        .next_stop
+                        .as_ref()
+                        .and_then(|id| schedule.stops.get(id))
+                    {
+                        row("Next stop", stop.name.clone());
+                    }
+                }
+                if let Some(trip) = vehicle.trip() {
+                    row("Trip", trip.trip_id.to_string());
+                    row("Delay", format!("{:+} min", delay::minutes(trip.delay)));
+                }
+                row(
+                    "Last update",
+                    format!(
+                        "{}, {}",
+                        vehicle.last_seen().with_timezone(&Local).format("%H:%M:%S"),
+                        vehicle::last_seen_ago(vehicle.age(Utc::now()))
+                    ),
+                );
+                row(
+                    "Speed",
+                    vehicle
+                        .speed()
+                        .map_or("unknown".to_string(), |speed| format!("{speed:.0} km/h")),
+                );
+                row("Positions", vehicle.fixes().len().to_string());
+                if !vehicle.line_changes().is_empty() {
+                    row("Lines served", vehicle.lines_served().join(" → "));
+                }
+            });
+        });
+
+    if !open {
+        *selected = None;
+    }
+}
+
+fn departures_board(ui: &mut Ui, departures: &[Departure]) {
+    if departures.is_empty() {
+        ui.label("No departures in the next two hours.");
+        return;
+    }
+
+    Grid::new("Departures").striped(true).show(ui, |ui| {
+        for departure in departures {
+            ui.strong(&departure.line);
+            ui.label(&departure.headsign);
+            ui.label(
+                departure
+                    .expected
+                    .with_timezone(&Local)
+                    .format("%H:%M")
+                    .to_string(),
+            );
+            if departure.live() {
+                let delay = departure.expected - departure.scheduled;
+                ui.colored_label(
+                    markers::punctuality_color(Punctuality::of(delay)),
+                    format!("live, {:+} min", delay::minutes(delay)),
+                );
+            } else {
+                ui.weak("scheduled");
+            }
+            ui.end_row();
+        }
+    });
+}
+
+/// How many vehicles the feed tracks, when they were updated and what went wrong, if anything.
+fn feed_status(ui: &mut Ui, name: &str, vehicles: usize, status: &FeedStatus) {
+    if let Some(last_success) = status.last_success {
+        ui.label(format!(
+            "{name}: {vehicles} vehicles, updated at {}.",
+            last_success.with_timezone(&Local).format("%H:%M:%S")
+        ))
+        .on_hover_text(format!(
+            "Polls: {} new, {} unchanged, {} failed.",
+            status.polls.new, status.polls.unchanged, status.polls.failed
+        ));
+    }
+
+    if let Some(err) = &status.last_error {
+        ui.colored_label(
+            ui.visuals().error_fg_color,
+            format!(
+                "{name}: {err} ({} failures in a row)",
+                status.consecutive_failures
+            ),
+        );
+    }
+}
+
+fn timetable_status(ui: &mut Ui, gtfs: &Gtfs) {
+    match gtfs.status() {
+        LoadStatus::Downloading => {
+            ui.label("Downloading the timetable.");
+        }
+        LoadStatus::Loading(file) => {
+            ui.label(format!("Loading the timetable ({file})."));
+        }
+        LoadStatus::Loaded => {
+            let schedule = gtfs.schedule();
+            ui.label(format!(
+                "Timetable has {} stops and {} trips.",
+                schedule.stops.len(),
+                schedule.trips.len()
+            ));
+        }
+        LoadStatus::Failed(err) => {
+            ui.colored_label(ui.visuals().error_fg_color, format!("Timetable: {err}"));
+        }
+    }
+}
+
 pub fn large_material_button(ui: &mut Ui, text: &str) -> Response {
     ui.button(RichText::new(text).size(24.0))
 }
 
-/// Simple GUI to zoom in and out.
-pub fn zoom(ui: &Ui, map_memory: &mut MapMemory) {
+/// Simple GUI to zoom in and out, and to go back to following the vehicle, if one is
+/// selected, or my position.
+pub fn zoom(ui: &Ui, map_memory: &mut MapMemory, following_vehicle: bool) {
     Window::new("Map")
         .collapsible(false)
         .resizable(false)
@@ -52,7 +444,13 @@ pub fn zoom(ui: &Ui, map_memory: &mut MapMemory) {
                 }
 
                 if map_memory.detached().is_some()
-                    && large_material_button(ui, "\u{e55c}").clicked()
+                    && large_material_button(ui, "\u{e55c}")
+                        .on_hover_text(if following_vehicle {
+                            "Follow the vehicle"
+                        } else {
+                            "Back to my position"
+                        })
+                        .clicked()
                 {
                     map_memory.follow_my_position();
                 }
@@ -0,0 +1,30 @@
+//! Kept apart from the other tests, because background mode is global.
+
+mod common;
+
+use chrono::Utc;
+use common::{dump, record, serve, start, wait_until, TestClock};
+use wrowalk::feed::{set_app_in_background, VehicleFeed as _};
+
+#[test]
+fn background_mode_clears_vehicles() {
+    let now = Utc::now();
+    let server = serve(vec![dump(&[record(2401, "1", 51.11, 17.03, now)])]);
+    let feed = start(&server, TestClock::new(now));
+
+    wait_until("vehicles appear", || !feed.vehicles().is_empty());
+
+    set_app_in_background(true);
+    wait_until("vehicles are cleared", || feed.vehicles().is_empty());
+
+    let requests = server.requests();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    assert_eq!(
+        server.requests(),
+        requests,
+        "feed should not poll in background"
+    );
+
+    set_app_in_background(false);
+    wait_until("vehicles are back", || !feed.vehicles().is_empty());
+}
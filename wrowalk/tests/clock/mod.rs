@@ -0,0 +1,35 @@
+//! Clock for the tests, which jumps forward instead of sleeping. Unit tests include it too,
+//! so it takes `Clock` and `Sleep` from the module including it, rather than from the crate.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use super::{Clock, Sleep};
+
+pub struct TestClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl TestClock {
+    pub fn new(now: DateTime<Utc>) -> Arc<Self> {
+        Arc::new(Self {
+            now: Mutex::new(now),
+        })
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        *self.now.lock().unwrap() += TimeDelta::from_std(duration).unwrap();
+        // Still yield for a bit, so the test can observe what is going on.
+        Box::pin(tokio::time::sleep(Duration::from_millis(1)))
+    }
+}
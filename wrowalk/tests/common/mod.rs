@@ -0,0 +1,115 @@
+//! Helpers shared by the integration tests. Not all of them use every helper.
+
+#![allow(dead_code)]
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use chrono_tz::Europe::Warsaw;
+use wrowalk::{
+    clock::{Clock, Sleep},
+    feed::VehicleFeed as _,
+    gtfs::Schedule,
+    mpkwroclaw::MpkWroclaw,
+};
+use wrowalk_mock::Server;
+
+#[path = "../timetable/mod.rs"]
+mod timetable;
+
+#[path = "../clock/mod.rs"]
+mod clock;
+
+pub use clock::TestClock;
+
+/// Single record of the MPK dump.
+pub fn record(
+    fleet_number: u32,
+    line: &str,
+    latitude: f64,
+    longitude: f64,
+    time: DateTime<Utc>,
+) -> String {
+    format!(
+        "{fleet_number},{fleet_number},None,00101,{line},{latitude},{longitude},{}",
+        time.with_timezone(&Warsaw).format("%Y-%m-%d %H:%M:%S%.6f")
+    )
+}
+
+/// MPK dump made of given records.
+pub fn dump(records: &[String]) -> String {
+    let mut dump = "_id,Nr_Boczny,Nr_Rej,Brygada,Nazwa_Linii,Ostatnia_Pozycja_Szerokosc,Ostatnia_Pozycja_Dlugosc,Data_Aktualizacji\n".to_string();
+    for record in records {
+        dump.push_str(record);
+        dump.push('\n');
+    }
+    dump
+}
+
+/// Serve given dumps, in rotation.
+pub fn serve(fixtures: Vec<String>) -> Server {
+    Server::start(
+        ("127.0.0.1", 0),
+        wrowalk_mock::Config {
+            fixtures,
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+/// Vehicles are kept for that long by the feeds of the tests, since [`TestClock`] runs much
+/// faster than the tests poll.
+const EVICT_AFTER: TimeDelta = TimeDelta::days(365);
+
+/// MPK feed, already polling the server.
+pub fn start(server: &Server, clock: Arc<TestClock>) -> MpkWroclaw {
+    start_evicting_after(server, clock, EVICT_AFTER)
+}
+
+/// MPK feed forgetting vehicles which were silent for given time, already polling the server.
+pub fn start_evicting_after(
+    server: &Server,
+    clock: Arc<TestClock>,
+    evict_after: TimeDelta,
+) -> MpkWroclaw {
+    let mut feed = MpkWroclaw::with_url(server.url()).with_clock(clock);
+    set_evict_after(&mut feed, evict_after);
+    feed.start(egui::Context::default());
+    feed
+}
+
+fn set_evict_after(feed: &mut MpkWroclaw, evict_after: TimeDelta) {
+    let mut settings = feed.settings();
+    settings.evict_after = evict_after;
+    feed.set_settings(settings);
+}
+
+/// Small timetable, see [`timetable::FILES`].
+pub fn schedule() -> Arc<Schedule> {
+    Arc::new(Schedule::parse(&timetable::zip(timetable::FILES)).unwrap())
+}
+
+/// MPK feed matching vehicles against the timetable, already polling the server.
+pub fn start_with_schedule(
+    server: &Server,
+    clock: Arc<TestClock>,
+    schedule: Arc<Schedule>,
+) -> MpkWroclaw {
+    let mut feed = MpkWroclaw::with_url(server.url()).with_clock(clock);
+    set_evict_after(&mut feed, EVICT_AFTER);
+    feed.set_schedule(schedule);
+    feed.start(egui::Context::default());
+    feed
+}
+
+pub fn wait_until(what: &str, condition: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out waiting until {what}");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,309 @@
+mod common;
+
+use chrono::{TimeDelta, TimeZone as _, Utc};
+use chrono_tz::Europe::Warsaw;
+use common::{
+    dump, record, schedule, serve, start, start_evicting_after, start_with_schedule, wait_until,
+    TestClock,
+};
+use wrowalk::feed::{FeedError, VehicleFeed as _};
+
+#[test]
+fn vehicles_appear_after_first_poll() {
+    let now = Utc::now();
+    let server = serve(vec![dump(&[
+        record(2401, "1", 51.11, 17.03, now),
+        record(8231, "145", 51.10, 17.02, now),
+    ])]);
+    let feed = start(&server, TestClock::new(now));
+
+    wait_until("vehicles appear", || feed.vehicles().len() == 2);
+
+    let vehicle = &feed.vehicles()["2401"];
+    assert_eq!(vehicle.line, "1");
+    assert_eq!(vehicle.position(), walkers::lat_lon(51.11, 17.03));
+    assert_eq!(
+        vehicle.last_seen().timestamp_micros(),
+        now.timestamp_micros()
+    );
+}
+
+#[test]
+fn registration_and_brigade_are_kept() {
+    let now = Utc::now();
+    let bus = record(8231, "145", 51.10, 17.02, now).replace(",None,", ",DW 12345,");
+    let server = serve(vec![dump(&[bus, record(2401, "1", 51.11, 17.03, now)])]);
+    let feed = start(&server, TestClock::new(now));
+
+    wait_until("vehicles appear", || feed.vehicles().len() == 2);
+    let vehicles = feed.vehicles();
+
+    let bus = &vehicles["8231"];
+    assert_eq!(bus.registration.as_deref(), Some("DW 12345"));
+    assert_eq!(bus.brigade.as_deref(), Some("00101"));
+    assert_eq!(vehicles["2401"].registration, None);
+}
+
+#[test]
+fn history_grows_and_is_capped() {
+    let now = Utc::now();
+    let fixtures = (0..15)
+        .map(|n| {
+            dump(&[record(
+                2401,
+                "1",
+                51.11 + n as f64 * 0.001,
+                17.03,
+                now + TimeDelta::seconds(n * 5),
+            )])
+        })
+        .collect();
+    let server = serve(fixtures);
+    let feed = start(&server, TestClock::new(now));
+
+    wait_until("history grows", || {
+        feed.vehicles()
+            .get("2401")
+            .is_some_and(|vehicle| vehicle.positions().len() > 2)
+    });
+    wait_until("all fixtures are served", || server.requests() > 15);
+
+    let vehicle = &feed.vehicles()["2401"];
+    assert_eq!(vehicle.positions().len(), 10);
+    assert_eq!(
+        vehicle.position(),
+        walkers::lat_lon(51.11 + 14.0 * 0.001, 17.03)
+    );
+}
+
+#[test]
+fn vehicle_keeps_identity_when_switching_lines() {
+    let now = Utc::now();
+    let later = now + TimeDelta::seconds(5);
+    let server = serve(vec![
+        dump(&[record(2401, "1", 51.11, 17.03, now)]),
+        dump(&[record(2401, "7", 51.12, 17.03, later)]),
+    ]);
+    let feed = start(&server, TestClock::new(now));
+
+    wait_until("line changes", || {
+        feed.vehicles()
+            .get("2401")
+            .is_some_and(|vehicle| vehicle.line == "7")
+    });
+
+    let vehicles = feed.vehicles();
+    assert_eq!(vehicles.len(), 1);
+    let vehicle = &vehicles["2401"];
+    assert_eq!(vehicle.positions().len(), 2);
+    assert_eq!(vehicle.lines_served(), vec!["1", "7"]);
+    assert_eq!(
+        vehicle.line_changes()[0].time.timestamp_micros(),
+        later.timestamp_micros()
+    );
+}
+
+#[test]
+fn speed_and_bearing_follow_the_history() {
+    let now = Utc::now();
+    // Heading north, 0.0005° of latitude (about 55.6 m) every 5 s, which is about 40 km/h.
+    let fixtures = (0..3)
+        .map(|n| {
+            dump(&[record(
+                2401,
+                "1",
+                51.11 + n as f64 * 0.0005,
+                17.03,
+                now + TimeDelta::seconds(n * 5),
+            )])
+        })
+        .collect();
+    let server = serve(fixtures);
+    let feed = start(&server, TestClock::new(now));
+
+    wait_until("history grows", || {
+        feed.vehicles()
+            .get("2401")
+            .is_some_and(|vehicle| vehicle.positions().len() == 3)
+    });
+
+    let vehicle = &feed.vehicles()["2401"];
+    let speed = vehicle.speed().unwrap();
+    assert!((speed - 40.0).abs() < 1.0, "speed is {speed}");
+    let bearing = vehicle.bearing().unwrap();
+    assert!(bearing.min(360.0 - bearing) < 1.0, "bearing is {bearing}");
+}
+
+#[test]
+fn positions_near_the_route_are_snapped() {
+    let now = Utc::now();
+    let server = serve(vec![dump(&[
+        // Few meters off the line between first two stops of line 1.
+        record(2401, "1", 51.1035, 17.0383, now),
+        // Far away, maybe on a diversion.
+        record(2402, "1", 51.1035, 17.0500, now),
+    ])]);
+    let feed = start_with_schedule(&server, TestClock::new(now), schedule());
+
+    wait_until("vehicles appear", || feed.vehicles().len() == 2);
+    let vehicles = feed.vehicles();
+
+    let near = &vehicles["2401"];
+    let fix = near.fixes()[0];
+    assert_eq!(fix.position, walkers::lat_lon(51.1035, 17.0383));
+    let snapped = fix.snapped.expect("position is snapped");
+    assert_ne!(snapped, fix.position);
+    assert_eq!(near.position(), snapped);
+
+    let far = &vehicles["2402"];
+    assert_eq!(far.fixes()[0].snapped, None);
+    assert_eq!(far.position(), walkers::lat_lon(51.1035, 17.0500));
+}
+
+#[test]
+fn vehicles_are_matched_to_trips() {
+    // Monday, half way between the second and the third stop of trip 3_1, which is due there
+    // at 8:07:45.
+    let now = Warsaw
+        .with_ymd_and_hms(2026, 10, 19, 8, 10, 45)
+        .unwrap()
+        .with_timezone(&Utc);
+    let server = serve(vec![dump(&[record(2401, "1", 51.1100, 17.0500, now)])]);
+    let feed = start_with_schedule(&server, TestClock::new(now), schedule());
+
+    wait_until("vehicle appears", || !feed.vehicles().is_empty());
+
+    let vehicle = &feed.vehicles()["2401"];
+    let trip = vehicle.trip().expect("vehicle is matched");
+    assert_eq!(&*trip.trip_id, "3_1");
+    assert_eq!(trip.next_stop, 2);
+    assert_eq!(trip.delay, TimeDelta::minutes(3));
+}
+
+#[test]
+fn direction_is_inferred_from_the_movement() {
+    // Sunday, when no trip of the timetable runs, so only the shapes can tell.
+    let now = Warsaw
+        .with_ymd_and_hms(2026, 10, 18, 12, 0, 0)
+        .unwrap()
+        .with_timezone(&Utc);
+    let later = now + TimeDelta::seconds(30);
+    let server = serve(vec![
+        dump(&[
+            // Leaving the first stop of line 1 towards Plac Grunwaldzki...
+            record(2401, "1", 51.1010, 17.0369, now),
+            // ...and going back to Dworzec Główny on the other shape.
+            record(2402, "1", 51.1081, 17.0528, now),
+        ]),
+        dump(&[
+            record(2401, "1", 51.1035, 17.0380, later),
+            record(2402, "1", 51.1055, 17.0480, later),
+        ]),
+    ]);
+    let feed = start_with_schedule(&server, TestClock::new(now), schedule());
+
+    wait_until("vehicles move", || {
+        feed.vehicles()
+            .values()
+            .filter(|vehicle| vehicle.fixes().len() == 2)
+            .count()
+            == 2
+    });
+    let vehicles = feed.vehicles();
+
+    let outbound = &vehicles["2401"];
+    assert!(outbound.trip().is_none());
+    let direction = outbound.direction().expect("direction is inferred");
+    assert_eq!(direction.headsign, "Plac Grunwaldzki");
+    assert_eq!(direction.next_stop.as_deref(), Some("2"));
+
+    let inbound = vehicles["2402"].direction().expect("direction is inferred");
+    assert_eq!(inbound.headsign, "Dworzec Główny");
+    assert_eq!(inbound.next_stop.as_deref(), Some("1"));
+}
+
+#[test]
+fn insane_records_are_dropped() {
+    let now = Utc::now();
+    let server = serve(vec![dump(&[
+        record(2401, "1", 51.11, 17.03, now),
+        record(2402, "None", 51.11, 17.03, now),
+        record(2403, "", 51.11, 17.03, now),
+        record(2404, "2", 0.0, 0.0, now),
+    ])]);
+    let feed = start(&server, TestClock::new(now));
+
+    wait_until("vehicles appear", || !feed.vehicles().is_empty());
+    wait_until("another poll is done", || server.requests() > 1);
+
+    assert_eq!(
+        feed.vehicles().keys().collect::<Vec<_>>(),
+        vec![&"2401".to_string()]
+    );
+}
+
+#[test]
+fn failures_do_not_stop_polling() {
+    let now = Utc::now();
+    let server = serve(vec![dump(&[record(2401, "1", 51.11, 17.03, now)])]);
+    server.configure(|config| config.faults.server_error = 1.0);
+    let feed = start(&server, TestClock::new(now));
+
+    wait_until("few polls fail", || feed.status().polls.failed >= 3);
+    let status = feed.status();
+    assert!(matches!(
+        status.last_error.as_deref(),
+        Some(FeedError::Status { .. })
+    ));
+    assert!(status.consecutive_failures >= 3);
+    assert!(feed.vehicles().is_empty());
+
+    server.configure(|config| config.faults.server_error = 0.0);
+
+    wait_until("vehicles appear", || !feed.vehicles().is_empty());
+    let status = feed.status();
+    assert!(status.last_error.is_none());
+    assert_eq!(status.consecutive_failures, 0);
+}
+
+#[test]
+fn unexpected_columns_are_reported() {
+    let now = Utc::now();
+    let server = serve(vec![dump(&[record(2401, "1", 51.11, 17.03, now)])]);
+    server.configure(|config| config.faults.bad_columns = 1.0);
+    let feed = start(&server, TestClock::new(now));
+
+    wait_until("poll fails", || feed.status().polls.failed >= 1);
+    assert!(matches!(
+        feed.status().last_error.as_deref(),
+        Some(FeedError::Schema(_))
+    ));
+}
+
+#[test]
+fn unchanged_dumps_are_skipped() {
+    let now = Utc::now();
+    let server = serve(vec![dump(&[record(2401, "1", 51.11, 17.03, now)])]);
+    let feed = start(&server, TestClock::new(now));
+
+    wait_until("few polls are done", || feed.status().polls.unchanged >= 2);
+    assert_eq!(feed.status().polls.new, 1);
+}
+
+#[test]
+fn silent_vehicles_are_evicted() {
+    let now = Utc::now();
+    let server = serve(vec![dump(&[
+        record(2401, "1", 51.11, 17.03, now),
+        record(2402, "2", 51.11, 17.03, now - TimeDelta::hours(1)),
+    ])]);
+    let feed = start_evicting_after(&server, TestClock::new(now), TimeDelta::minutes(15));
+
+    wait_until("vehicles appear", || !feed.vehicles().is_empty());
+    assert!(!feed.vehicles().contains_key("2402"));
+
+    // Clock moves on while the dump stays the same.
+    wait_until("remaining vehicle is evicted", || {
+        feed.vehicles().is_empty()
+    });
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use chrono::{DateTime, Utc};
+use common::{wait_until, TestClock};
+use wrowalk::{feed::VehicleFeed as _, gtfs_realtime::GtfsRealtime};
+use wrowalk_mock::Server;
+
+/// Two vehicles, one deleted entity and one without a position.
+const FIXTURE: &[u8] = include_bytes!("../../wrowalk_mock/fixtures/vehicle_positions.pb");
+
+/// When the fixture was recorded.
+fn recorded() -> DateTime<Utc> {
+    DateTime::from_timestamp(1_760_785_200, 0).unwrap()
+}
+
+fn start(server: &Server) -> GtfsRealtime {
+    let mut feed =
+        GtfsRealtime::new("GTFS-RT", server.realtime_url()).with_clock(TestClock::new(recorded()));
+    feed.start(egui::Context::default());
+    feed
+}
+
+fn serve() -> Server {
+    Server::start(
+        ("127.0.0.1", 0),
+        wrowalk_mock::Config {
+            realtime: vec![FIXTURE.to_vec()],
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+#[test]
+fn vehicles_appear_after_first_poll() {
+    let server = serve();
+    let feed = start(&server);
+
+    wait_until("vehicles appear", || feed.vehicles().len() == 2);
+
+    let vehicle = &feed.vehicles()["2401"];
+    assert_eq!(vehicle.line, "1");
+    assert_eq!(vehicle.registration.as_deref(), Some("DW 12345"));
+    assert_eq!(
+        vehicle.position(),
+        walkers::lat_lon(51.11_f32 as f64, 17.03_f32 as f64)
+    );
+    assert_eq!(vehicle.last_seen(), recorded());
+}
+
+#[test]
+fn unchanged_feed_is_skipped() {
+    let server = serve();
+    let feed = start(&server);
+
+    wait_until("few polls are done", || feed.status().polls.unchanged >= 2);
+    assert_eq!(feed.status().polls.new, 1);
+}
@@ -0,0 +1,88 @@
+//! Small GTFS timetable for the tests, zipped on the fly. Unit tests include it too, so it
+//! does not depend on the crate.
+
+#![allow(dead_code)]
+
+use std::io::Write as _;
+
+/// Three stops of line 1, with a night trip back after midnight, and a bus calling at one
+/// of them.
+pub const FILES: &[(&str, &str)] = &[
+    (
+        "agency.txt",
+        "agency_id,agency_name,agency_url,agency_timezone
+2,MPK Wrocław,http://www.mpk.wroc.pl,Europe/Warsaw
+",
+    ),
+    (
+        "stops.txt",
+        "stop_id,stop_code,stop_name,stop_lat,stop_lon
+1,10001,Dworzec Główny,51.0990,17.0360
+2,10002,Galeria Dominikańska,51.1080,17.0400
+3,10003,Plac Grunwaldzki,51.1120,17.0600
+",
+    ),
+    (
+        "routes.txt",
+        "route_id,agency_id,route_short_name,route_long_name,route_type
+1,2,1,Dworzec Główny - Plac Grunwaldzki,0
+145,2,145,Sępolno - Kozanów,3
+",
+    ),
+    (
+        "calendar.txt",
+        "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date
+6,1,1,1,1,1,0,0,20250101,20301231
+",
+    ),
+    (
+        "calendar_dates.txt",
+        "service_id,date,exception_type
+6,20260101,2
+6,20261017,1
+",
+    ),
+    (
+        "trips.txt",
+        "route_id,service_id,trip_id,trip_headsign,direction_id,shape_id,brigade_id
+1,6,3_1,Plac Grunwaldzki,0,1,101
+1,6,3_2,Dworzec Główny,1,2,101
+145,6,7_1,Kozanów,0,,14501
+",
+    ),
+    (
+        "shapes.txt",
+        "shape_id,shape_pt_lat,shape_pt_lon,shape_pt_sequence
+1,51.1080,17.0400,2
+1,51.0990,17.0360,1
+1,51.1120,17.0600,3
+2,51.1120,17.0600,1
+2,51.0990,17.0360,2
+",
+    ),
+    (
+        "stop_times.txt",
+        "trip_id,arrival_time,departure_time,stop_id,stop_sequence
+3_1,08:00:00,08:00:00,1,1
+3_1,08:10:00,08:10:00,3,3
+3_1,08:05:00,08:05:30,2,2
+3_2,24:10:00,24:10:00,3,1
+3_2,24:20:00,24:20:00,1,2
+7_1,09:00:00,09:00:00,2,1
+7_1,09:06:00,09:06:00,1,2
+",
+    ),
+];
+
+/// ZIP archive of given files, deflated.
+pub fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    for (name, contents) in files {
+        writer.start_file(*name, options).unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
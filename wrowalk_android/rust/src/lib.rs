@@ -12,11 +12,18 @@ fn android_main(
     );
     let mut options = NativeOptions::default();
     options.renderer = Renderer::Wgpu;
+    // There is no default place for the settings and the cache on Android.
+    options.persistence_path = app.internal_data_path().map(|dir| dir.join("wrowalk.ron"));
+    let cache_dir = app.internal_data_path().map(|dir| dir.join("cache"));
     options.android_app = Some(app);
     eframe::run_native(
         "Wrowalk",
         options,
-        Box::new(|cc| Ok(Box::new(wrowalk::MyApp::new(cc.egui_ctx.clone())))),
+        Box::new(|cc| {
+            Ok(Box::new(
+                wrowalk::MyApp::with_cache_dir(cc.egui_ctx.clone(), cache_dir).restore(cc.storage),
+            ))
+        }),
     )?;
 
     Ok(())
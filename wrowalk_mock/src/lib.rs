@@ -0,0 +1,325 @@
+//! Stand-in for the Wrocław Open Data portal, serving vehicle dumps for development and tests.
+//! Recorded GTFS-Realtime feeds can be served along, for operators other than MPK Wrocław.
+
+mod synthetic;
+
+use std::{
+    hash::{DefaultHasher, Hash as _, Hasher as _},
+    io,
+    net::ToSocketAddrs,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use tiny_http::{Header, Method, Request, Response, ResponseBox};
+
+/// Path of the vehicle dump, the same as on the real portal.
+pub const DUMP_PATH: &str = "/open-data/datastore/dump/a9b3841d-e977-474e-9e86-8789e470a85a";
+
+/// Path of the GTFS-Realtime `VehiclePositions` feed.
+pub const REALTIME_PATH: &str = "/gtfs-rt/vehicle_positions.pb";
+
+const HEADER: &str = "_id,Nr_Boczny,Nr_Rej,Brygada,Nazwa_Linii,Ostatnia_Pozycja_Szerokosc,Ostatnia_Pozycja_Dlugosc,Data_Aktualizacji\n";
+
+/// Header of a dump with columns that wrowalk does not know.
+const BAD_HEADER: &str = "_id,Numer,Rejestracja,Brygada,Linia,Szerokosc,Dlugosc,Aktualizacja\n";
+
+/// Probabilities, from 0.0 to 1.0, of faults being injected into a response.
+#[derive(Debug, Clone, Default)]
+pub struct Faults {
+    /// Respond with 500 Internal Server Error.
+    pub server_error: f64,
+    /// Cut the body in half.
+    pub truncated: f64,
+    /// Serve the dump with unexpected column names. Does not apply to GTFS-Realtime.
+    pub bad_columns: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// CSV dumps, served in rotation, one per request.
+    pub fixtures: Vec<String>,
+    /// Recorded GTFS-Realtime `VehiclePositions` messages, served as they are, in rotation.
+    pub realtime: Vec<Vec<u8>>,
+    /// Replace `Data_Aktualizacji` of the fixtures with the current time, so that recorded
+    /// vehicles do not look stale.
+    pub restamp: bool,
+    /// Number of made up vehicles, riding along a few streets, added to every dump.
+    pub synthetic_vehicles: usize,
+    /// Delay before every response.
+    pub latency: Duration,
+    pub faults: Faults,
+}
+
+struct State {
+    config: Config,
+    requests: usize,
+    realtime_requests: usize,
+    started: Instant,
+}
+
+/// Serves the dump in a background thread, until dropped.
+pub struct Server {
+    server: Arc<tiny_http::Server>,
+    state: Arc<Mutex<State>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    /// Start serving on given address. Use port 0 to pick a free one.
+    pub fn start(addr: impl ToSocketAddrs, config: Config) -> io::Result<Self> {
+        let server = Arc::new(tiny_http::Server::http(addr).map_err(io::Error::other)?);
+        let state = Arc::new(Mutex::new(State {
+            config,
+            requests: 0,
+            realtime_requests: 0,
+            started: Instant::now(),
+        }));
+
+        let thread = std::thread::spawn({
+            let server = server.clone();
+            let state = state.clone();
+            move || {
+                for request in server.incoming_requests() {
+                    let state = state.clone();
+                    std::thread::spawn(move || {
+                        if let Err(err) = handle(request, &state) {
+                            log::warn!("Could not respond: {err}");
+                        }
+                    });
+                }
+            }
+        });
+
+        Ok(Self {
+            server,
+            state,
+            thread: Some(thread),
+        })
+    }
+
+    /// URL of the dump, to be passed to wrowalk.
+    pub fn url(&self) -> String {
+        self.url_of(DUMP_PATH)
+    }
+
+    /// URL of the GTFS-Realtime feed, to be passed to wrowalk.
+    pub fn realtime_url(&self) -> String {
+        self.url_of(REALTIME_PATH)
+    }
+
+    fn url_of(&self, path: &str) -> String {
+        let addr = self
+            .server
+            .server_addr()
+            .to_ip()
+            .expect("server listens on an IP address");
+        format!("http://{addr}{path}")
+    }
+
+    /// Change the configuration while the server is running.
+    pub fn configure(&self, f: impl FnOnce(&mut Config)) {
+        f(&mut self.state.lock().unwrap().config);
+    }
+
+    /// Number of requests for the dump received so far.
+    pub fn requests(&self) -> usize {
+        self.state.lock().unwrap().requests
+    }
+
+    /// Number of requests for the GTFS-Realtime feed received so far.
+    pub fn realtime_requests(&self) -> usize {
+        self.state.lock().unwrap().realtime_requests
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn handle(request: Request, state: &Mutex<State>) -> io::Result<()> {
+    let response = if request.method() == &Method::Options {
+        Response::empty(204).boxed()
+    } else if request.url() == DUMP_PATH {
+        dump_response(&request, state)
+    } else if request.url() == REALTIME_PATH {
+        realtime_response(&request, state)
+    } else {
+        Response::empty(404).boxed()
+    };
+
+    request.respond(with_cors(response))
+}
+
+fn dump_response(request: &Request, state: &Mutex<State>) -> ResponseBox {
+    let (body, latency, faults) = {
+        let mut state = state.lock().unwrap();
+        state.requests += 1;
+        (
+            dump(&state),
+            state.config.latency,
+            state.config.faults.clone(),
+        )
+    };
+
+    match faulty(body.into_bytes(), latency, &faults, Some(with_bad_header)) {
+        Ok(body) => cacheable(request, body, "text/csv"),
+        Err(response) => response,
+    }
+}
+
+fn realtime_response(request: &Request, state: &Mutex<State>) -> ResponseBox {
+    let (body, latency, faults) = {
+        let mut state = state.lock().unwrap();
+        let realtime = &state.config.realtime;
+        if realtime.is_empty() {
+            return Response::empty(404).boxed();
+        }
+        let body = realtime[state.realtime_requests % realtime.len()].clone();
+        state.realtime_requests += 1;
+        (body, state.config.latency, state.config.faults.clone())
+    };
+
+    match faulty(body, latency, &faults, None) {
+        Ok(body) => cacheable(request, body, "application/x-protobuf"),
+        Err(response) => response,
+    }
+}
+
+/// Wait for the latency, then maybe inject one of the faults: respond with an error, or
+/// mangle the body. Bad columns are only injected if the format has columns to mangle.
+fn faulty(
+    body: Vec<u8>,
+    latency: Duration,
+    faults: &Faults,
+    bad_columns: Option<fn(Vec<u8>) -> Vec<u8>>,
+) -> Result<Vec<u8>, ResponseBox> {
+    std::thread::sleep(latency);
+
+    let roll = fastrand::f64();
+
+    if roll < faults.server_error {
+        log::info!("Injecting 500.");
+        return Err(Response::from_string("Internal Server Error")
+            .with_status_code(500)
+            .boxed());
+    }
+
+    let body = if roll < faults.server_error + faults.truncated {
+        log::info!("Injecting truncated body.");
+        body[..body.len() / 2].to_vec()
+    } else {
+        match bad_columns {
+            Some(bad_columns)
+                if roll < faults.server_error + faults.truncated + faults.bad_columns =>
+            {
+                log::info!("Injecting bad columns.");
+                bad_columns(body)
+            }
+            _ => body,
+        }
+    };
+
+    Ok(body)
+}
+
+/// The dump with its header replaced by [`BAD_HEADER`].
+fn with_bad_header(dump: Vec<u8>) -> Vec<u8> {
+    let dump = String::from_utf8(dump).expect("dump is UTF-8");
+    let (_, records) = dump.split_once('\n').unwrap_or_default();
+    format!("{BAD_HEADER}{records}").into_bytes()
+}
+
+/// Respond with the body, or with 304 Not Modified if the client already has it.
+fn cacheable(request: &Request, body: Vec<u8>, content_type: &str) -> ResponseBox {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    let etag = format!("\"{:x}\"", hasher.finish());
+
+    let not_modified = request
+        .headers()
+        .iter()
+        .any(|h| h.field.equiv("If-None-Match") && h.value.as_str() == etag);
+
+    let response = if not_modified {
+        Response::empty(304).boxed()
+    } else {
+        Response::from_data(body)
+            .with_header(header("Content-Type", content_type))
+            .boxed()
+    };
+
+    response.with_header(header("ETag", &etag))
+}
+
+/// Let wrowalk_web, served from a different origin, read the dump.
+fn with_cors(mut response: ResponseBox) -> ResponseBox {
+    response.add_header(header("Access-Control-Allow-Origin", "*"));
+    response.add_header(header(
+        "Access-Control-Allow-Headers",
+        "If-None-Match, If-Modified-Since",
+    ));
+    response.add_header(header("Access-Control-Expose-Headers", "ETag"));
+    response
+}
+
+/// Next fixture from the rotation, with synthetic vehicles added.
+fn dump(state: &State) -> String {
+    let config = &state.config;
+
+    let mut dump = if config.fixtures.is_empty() {
+        HEADER.to_string()
+    } else {
+        let fixture = &config.fixtures[(state.requests - 1) % config.fixtures.len()];
+        let mut fixture = if config.restamp {
+            restamp(fixture, Utc::now())
+        } else {
+            fixture.clone()
+        };
+        if !fixture.ends_with('\n') {
+            fixture.push('\n');
+        }
+        fixture
+    };
+
+    dump.push_str(&synthetic::records(
+        config.synthetic_vehicles,
+        state.started.elapsed(),
+        Utc::now(),
+    ));
+    dump
+}
+
+/// Set `Data_Aktualizacji` of every record to `now`.
+fn restamp(fixture: &str, now: DateTime<Utc>) -> String {
+    let mut lines = fixture.lines();
+    let Some(header) = lines.next() else {
+        return fixture.to_string();
+    };
+    let Some(column) = header.split(',').position(|c| c == "Data_Aktualizacji") else {
+        return fixture.to_string();
+    };
+    let timestamp = synthetic::timestamp(now);
+
+    std::iter::once(header.to_string())
+        .chain(lines.map(|line| {
+            line.split(',')
+                .enumerate()
+                .map(|(n, value)| if n == column { &timestamp } else { value })
+                .collect::<Vec<_>>()
+                .join(",")
+        }))
+        .map(|line| line + "\n")
+        .collect()
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field, value).expect("header is valid")
+}
@@ -0,0 +1,95 @@
+//! Serves vehicle dumps the way the Wrocław Open Data portal does, for wrowalk development.
+//! Point wrowalk at it with `WROWALK_MPK_URL`, which is printed on start.
+
+use std::time::Duration;
+
+use wrowalk_mock::{Config, Server};
+
+const USAGE: &str = "\
+Usage: wrowalk_mock [OPTIONS] [FIXTURE.csv|FIXTURE.pb...]
+
+Fixtures are served in rotation, one per request. CSV dumps are served as MPK Wrocław does,
+recorded GTFS-Realtime vehicle positions (.pb) are served as they are, on a separate URL.
+
+Options:
+  --port PORT            Port to listen on [default: 8080]
+  --synthetic COUNT      Made up vehicles added to every dump [default: 20]
+  --latency MILLIS       Delay before every response [default: 0]
+  --server-errors P      Probability of responding with 500 [default: 0]
+  --truncated P          Probability of cutting the body in half [default: 0]
+  --bad-columns P        Probability of serving unexpected columns [default: 0]
+  --keep-timestamps      Serve fixtures with their original timestamps
+
+Example, serving a few recorded vehicles around the centre along with the made up ones:
+  cargo run -p wrowalk_mock -- wrowalk_mock/fixtures/vehicles.csv";
+
+fn main() {
+    env_logger::init();
+
+    let mut port = 8080;
+    let mut config = Config {
+        synthetic_vehicles: 20,
+        restamp: true,
+        ..Default::default()
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = value(&mut args, &arg),
+            "--synthetic" => config.synthetic_vehicles = value(&mut args, &arg),
+            "--latency" => config.latency = Duration::from_millis(value(&mut args, &arg)),
+            "--server-errors" => config.faults.server_error = value(&mut args, &arg),
+            "--truncated" => config.faults.truncated = value(&mut args, &arg),
+            "--bad-columns" => config.faults.bad_columns = value(&mut args, &arg),
+            "--keep-timestamps" => config.restamp = false,
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                return;
+            }
+            path if path.ends_with(".pb") => {
+                config
+                    .realtime
+                    .push(std::fs::read(path).unwrap_or_else(|err| {
+                        exit_with_usage(&format!("could not read {path}: {err}"))
+                    }))
+            }
+            path if !path.starts_with('-') => {
+                config
+                    .fixtures
+                    .push(std::fs::read_to_string(path).unwrap_or_else(|err| {
+                        exit_with_usage(&format!("could not read {path}: {err}"))
+                    }))
+            }
+            _ => exit_with_usage(&format!("unknown option {arg}")),
+        }
+    }
+
+    let realtime = !config.realtime.is_empty();
+    let server = Server::start(("127.0.0.1", port), config).unwrap_or_else(|err| {
+        eprintln!("Could not start the server: {err}");
+        std::process::exit(1);
+    });
+
+    println!("Serving at {}", server.url());
+    println!("Run wrowalk with WROWALK_MPK_URL={}", server.url());
+    if realtime {
+        println!("and GTFS_RT_URL={}", server.realtime_url());
+    }
+
+    loop {
+        std::thread::park();
+    }
+}
+
+/// Value of the option `arg`, which is the next argument.
+fn value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, arg: &str) -> T {
+    args.next()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| exit_with_usage(&format!("invalid value for {arg}")))
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{message}\n\n{USAGE}");
+    std::process::exit(2);
+}
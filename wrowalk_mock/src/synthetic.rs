@@ -0,0 +1,112 @@
+//! Made up vehicles riding back and forth along a few streets of Wrocław.
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Europe::Warsaw;
+use std::time::Duration;
+
+/// Lines and the polylines, as (latitude, longitude), they run along.
+const ROUTES: &[(&str, &[(f64, f64)])] = &[
+    (
+        "S1",
+        &[
+            (51.1090, 17.0200),
+            (51.1095, 17.0330),
+            (51.1110, 17.0450),
+            (51.1135, 17.0600),
+        ],
+    ),
+    (
+        "S2",
+        &[
+            (51.1210, 17.0330),
+            (51.1110, 17.0320),
+            (51.1030, 17.0320),
+            (51.0990, 17.0360),
+            (51.0900, 17.0400),
+        ],
+    ),
+    (
+        "S3",
+        &[
+            (51.1100, 17.0200),
+            (51.1160, 17.0000),
+            (51.1210, 16.9800),
+            (51.1280, 16.9500),
+        ],
+    ),
+    (
+        "S4",
+        &[
+            (51.1010, 17.0280),
+            (51.0960, 17.0100),
+            (51.0900, 16.9900),
+            (51.0850, 16.9700),
+        ],
+    ),
+];
+
+/// Speed of every synthetic vehicle, in meters per second.
+const SPEED: f64 = 8.0;
+
+/// Distance between consecutive vehicles of the same line, in meters.
+const SPACING: f64 = 700.0;
+
+/// Good enough for distances within a city.
+fn distance((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_000.0;
+    let x = (lon2 - lon1).to_radians() * ((lat1 + lat2) / 2.0).to_radians().cos();
+    let y = (lat2 - lat1).to_radians();
+    x.hypot(y) * EARTH_RADIUS
+}
+
+/// Point `along` meters from the start of the polyline.
+fn point_along(polyline: &[(f64, f64)], mut along: f64) -> (f64, f64) {
+    for segment in polyline.windows(2) {
+        let length = distance(segment[0], segment[1]);
+        if along <= length {
+            let t = along / length;
+            return (
+                segment[0].0 + (segment[1].0 - segment[0].0) * t,
+                segment[0].1 + (segment[1].1 - segment[0].1) * t,
+            );
+        }
+        along -= length;
+    }
+    *polyline.last().unwrap()
+}
+
+/// Time formatted the way the portal does, in the local time of Wrocław.
+pub(crate) fn timestamp(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Warsaw)
+        .format("%Y-%m-%d %H:%M:%S%.6f")
+        .to_string()
+}
+
+/// CSV records, without the header, of `count` vehicles after `elapsed` time of riding.
+pub(crate) fn records(count: usize, elapsed: Duration, now: DateTime<Utc>) -> String {
+    let timestamp = timestamp(now);
+
+    (0..count)
+        .map(|n| {
+            let (line, polyline) = ROUTES[n % ROUTES.len()];
+            let length: f64 = polyline.windows(2).map(|s| distance(s[0], s[1])).sum();
+
+            // Ride to the end of the line and back.
+            let ridden = (SPEED * elapsed.as_secs_f64() + SPACING * (n / ROUTES.len()) as f64)
+                % (2.0 * length);
+            let along = if ridden < length {
+                ridden
+            } else {
+                2.0 * length - ridden
+            };
+            let (latitude, longitude) = point_along(polyline, along);
+
+            format!(
+                "{id},{fleet_number},None,{line}{brigade:02},{line},{latitude:.6},{longitude:.6},{timestamp}\n",
+                id = 9000 + n,
+                fleet_number = 9000 + n,
+                brigade = n / ROUTES.len() + 1,
+            )
+        })
+        .collect()
+}
@@ -7,7 +7,11 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "Wrowalk",
         Default::default(),
-        Box::new(|cc| Ok(Box::new(MyApp::new(cc.egui_ctx.clone())))),
+        Box::new(|cc| {
+            Ok(Box::new(
+                MyApp::new(cc.egui_ctx.clone()).restore(cc.storage),
+            ))
+        }),
     )
 }
 
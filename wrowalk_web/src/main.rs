@@ -24,7 +24,11 @@ fn main() {
             .start(
                 canvas,
                 web_options,
-                Box::new(|cc| Ok(Box::new(wrowalk::MyApp::new(cc.egui_ctx.clone())))),
+                Box::new(|cc| {
+                    Ok(Box::new(
+                        wrowalk::MyApp::new(cc.egui_ctx.clone()).restore(cc.storage),
+                    ))
+                }),
             )
             .await
             .expect("failed to start eframe");